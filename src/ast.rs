@@ -1,4 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::bench::Benchmarks;
+use crate::diagnostic::Span;
+use crate::events::Timers;
+use crate::logging::Logger;
+use crate::macros::Macro;
+use crate::memo::MemoCache;
+use crate::output::Output;
+use crate::prompt::Input;
+use crate::random::Rng;
+use crate::shared::{Access, SharedTable};
+use crate::stats::Stats;
+use crate::timing::LineTimes;
+use crate::terminal::Progress;
+
+/// A map of function names to their parameter list and body.
+///
+/// Bodies are shared rather than copied for each call, so a statement keeps
+/// its address for as long as its function is defined.
+pub type FunctionTable = HashMap<String, (Vec<String>, Rc<Vec<Stmt>>)>;
 
 /// Stores the runtime context for the interpreter, including variables and user-defined functions.
 #[derive(Default)]
@@ -6,7 +32,278 @@ pub struct Context {
     /// A map of variable names to their string values.
     pub variables: HashMap<String, String>,
     /// A map of function names to their parameter list and body.
-    pub functions: HashMap<String, (Vec<String>, Vec<Stmt>)>,
+    pub functions: FunctionTable,
+    /// The active function calls, innermost last.
+    pub frames: Vec<Frame>,
+    /// Where `print` writes.
+    pub output: Output,
+    /// Destination and level filter for the `log_*` builtins.
+    pub logger: Logger,
+    /// Where `confirm`, `select` and `password` ask their questions.
+    pub input: Input,
+    /// Directory of the running script; `import` looks here first.
+    pub base_dir: PathBuf,
+    /// Further directories searched by `import`, in order.
+    pub module_paths: Vec<PathBuf>,
+    /// Package dependencies, mapped to their entry scripts. `import` checks
+    /// these before searching for a file.
+    pub packages: HashMap<String, PathBuf>,
+    /// Functions of every module loaded so far, keyed by the module's file path.
+    pub modules: HashMap<PathBuf, FunctionTable>,
+    /// Result caches of the `@memo` functions of each loaded module, by
+    /// unqualified function name.
+    pub(crate) module_memos: HashMap<PathBuf, HashMap<String, MemoCache>>,
+    /// The modules `import` is running, outermost first, with the names they
+    /// were imported by.
+    pub(crate) loading_modules: Vec<(PathBuf, String)>,
+    /// Modules parsed ahead of time, keyed by file path; `import` uses these
+    /// instead of reading the file.
+    pub parsed_modules: HashMap<PathBuf, Vec<Stmt>>,
+    /// Namespaces created by `import`, mapped to the module they refer to.
+    pub namespaces: HashMap<String, String>,
+    /// Builtins that scripts may not call.
+    pub disabled_builtins: HashSet<String>,
+    /// Capabilities that are off unless the host enables them.
+    pub permissions: Permissions,
+    /// Source code run before the program in every `run_with_context`.
+    pub prelude: Option<String>,
+    /// Flags that select `@if(flag)` blocks.
+    pub defines: HashSet<String>,
+    /// Functions scheduled by `set_timeout` and `set_interval`.
+    pub timers: Timers,
+    /// Functions registered with `on_exit`, run by `run_exit_hooks`.
+    pub exit_hooks: Vec<String>,
+    /// Functions registered with `on_interrupt`, run when the script is interrupted.
+    pub interrupt_hooks: Vec<String>,
+    /// Files and directories made by `temp_file` and `temp_dir`, deleted by
+    /// `run_exit_hooks`.
+    pub temp_paths: Vec<PathBuf>,
+    /// Statement templates registered with `Context::define_macro`, by name.
+    pub macros: HashMap<String, Macro>,
+    /// Tables mounted with `Context::mount`, by the name scripts use.
+    pub shared_tables: HashMap<String, (SharedTable, Access)>,
+    /// The file `store_get`, `store_set` and `store_delete` keep their
+    /// entries in. Defaults to `store::DEFAULT_STORE_FILE` in `base_dir`.
+    pub store_path: Option<PathBuf>,
+    /// Source of randomness for `uuid4`, `random_hex` and `nanoid`.
+    pub rng: Rng,
+    /// Set by `dash bench` to time `bench` blocks and collect their results.
+    pub benchmarks: Option<Benchmarks>,
+    /// The bar started by `progress_start`, if any.
+    pub progress: Option<Progress>,
+    /// Result caches of the functions defined with `@memo`, by function name.
+    pub(crate) memo: HashMap<String, MemoCache>,
+    /// Setting this flag, e.g. from another thread, interrupts the script at
+    /// its next statement.
+    pub interrupt: Arc<AtomicBool>,
+    /// The script is interrupted at the first statement after this time.
+    /// Its `on_interrupt` hooks then get `eval::INTERRUPT_GRACE` more to finish.
+    pub deadline: Option<Instant>,
+    /// Executing more statements than this is a runtime error.
+    pub statement_limit: Option<u64>,
+    /// Calling functions nested deeper than this is a runtime error.
+    pub depth_limit: Option<usize>,
+    /// Building a string longer than this many bytes with `repeat`,
+    /// `pad_left` or `pad_right` is a runtime error.
+    pub string_limit: Option<usize>,
+    /// What a runtime error in a top-level statement does.
+    pub error_mode: ErrorMode,
+    /// Runtime errors reported and skipped over in `ErrorMode::Continue`.
+    pub errors: Vec<String>,
+    /// Reports each error skipped in `ErrorMode::Continue`. Without one,
+    /// errors are written to stderr as `Runtime error: <message>`.
+    pub report_error: Option<fn(&str)>,
+    /// Set by `--time-lines` to record the time spent in each statement.
+    pub line_times: Option<LineTimes>,
+    /// Execution counters, readable through `Context::stats`.
+    pub(crate) stats: Stats,
+}
+
+/// What happens when a top-level statement fails with a runtime error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorMode {
+    /// Stop the script.
+    #[default]
+    Abort,
+    /// Report the error to stderr, record it in `Context::errors` and go on
+    /// with the next top-level statement. Interrupts still stop the script.
+    Continue,
+}
+
+impl FromStr for ErrorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "abort" => Ok(ErrorMode::Abort),
+            "continue" => Ok(ErrorMode::Continue),
+            _ => Err(format!("Unknown error mode: {} (expected abort or continue)", s)),
+        }
+    }
+}
+
+/// Capabilities a script only has if the host grants them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permissions {
+    /// Allow `eval(code)`, which runs source code built at runtime.
+    pub allow_eval: bool,
+    /// Allow builtins that read the filesystem, such as `glob`, `absolute` and
+    /// a runtime `include_str`. The `dash` command always allows them.
+    pub allow_fs: bool,
+}
+
+/// Configures a `Context` for embedding, controlling which builtins scripts
+/// can use and what code runs before them.
+///
+/// ```
+/// use dash_lang::{run_with_context, ContextBuilder};
+///
+/// let mut ctx = ContextBuilder::new()
+///     .without_builtin("log_debug")
+///     .prelude("fn double(x) {\n  return x * 2\n}")
+///     .build();
+/// run_with_context("let y = double(21)", &mut ctx);
+/// assert_eq!(ctx.variables["y"], "42");
+/// ```
+#[derive(Default, Clone)]
+pub struct ContextBuilder {
+    disabled_builtins: HashSet<String>,
+    prelude: Vec<String>,
+    defines: HashSet<String>,
+    permissions: Permissions,
+    error_mode: ErrorMode,
+    seed: Option<u64>,
+    shared_tables: HashMap<String, (SharedTable, Access)>,
+    macros: HashMap<String, Macro>,
+}
+
+impl ContextBuilder {
+    /// Creates a builder for a context with every builtin enabled and no prelude.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the named builtin unavailable, as if it didn't exist.
+    pub fn without_builtin(mut self, name: &str) -> Self {
+        self.disabled_builtins.insert(name.to_string());
+        self
+    }
+
+    /// Adds source code to run before every program. Preludes added by
+    /// several calls run in the order they were added.
+    pub fn prelude(mut self, source: &str) -> Self {
+        self.prelude.push(source.to_string());
+        self
+    }
+
+    /// Defines a flag, so that `@if(name) { ... }` blocks are included.
+    pub fn define(mut self, name: &str) -> Self {
+        self.defines.insert(name.to_string());
+        self
+    }
+
+    /// Grants the capabilities in `permissions`.
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Sets what a runtime error in a top-level statement does.
+    pub fn error_mode(mut self, mode: ErrorMode) -> Self {
+        self.error_mode = mode;
+        self
+    }
+
+    /// Seeds the context's random number generator, so that the ids scripts
+    /// generate are the same on every run.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Mounts a shared table in every context built, as with `Context::mount`.
+    pub fn mount(mut self, name: &str, table: SharedTable, access: Access) -> Self {
+        self.shared_tables.insert(name.to_string(), (table, access));
+        self
+    }
+
+    /// Registers a macro in every context built, as with `Context::define_macro`.
+    pub fn define_macro(mut self, name: &str, definition: Macro) -> Self {
+        self.macros.insert(name.to_string(), definition);
+        self
+    }
+
+    /// Creates the configured context.
+    pub fn build(self) -> Context {
+        Context {
+            disabled_builtins: self.disabled_builtins,
+            prelude: (!self.prelude.is_empty()).then(|| self.prelude.join("\n")),
+            defines: self.defines,
+            permissions: self.permissions,
+            error_mode: self.error_mode,
+            rng: self.seed.map(Rng::new).unwrap_or_default(),
+            shared_tables: self.shared_tables,
+            macros: self.macros,
+            ..Context::default()
+        }
+    }
+}
+
+impl Context {
+    /// Makes `table` available to scripts under `name`, replacing any table
+    /// already mounted there. The table isn't copied: changes made through
+    /// any context or by the host are seen by all of them.
+    pub fn mount(&mut self, name: &str, table: SharedTable, access: Access) {
+        self.shared_tables.insert(name.to_string(), (table, access));
+    }
+
+    /// Lets scripts write `name(args) { ... }` to run `definition`,
+    /// replacing any macro already registered under `name`.
+    pub fn define_macro(&mut self, name: &str, definition: Macro) {
+        self.macros.insert(name.to_string(), definition);
+    }
+
+    /// Appends a directory to the module search path used by `import`.
+    pub fn add_module_path(&mut self, path: impl Into<PathBuf>) {
+        self.module_paths.push(path.into());
+    }
+
+    /// Returns the execution counters collected so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Sets a variable in the current scope, keeping memory statistics up to date.
+    pub fn set_variable(&mut self, name: &str, value: String) {
+        let new = value.len();
+        let old = self
+            .variables
+            .insert(name.to_string(), value)
+            .map_or(0, |old| old.len());
+        self.stats.replace_value(old, new);
+    }
+
+    /// Pops call frames until only `depth` remain, restoring the variables of
+    /// the scope that was active at that depth.
+    ///
+    /// This is used after a runtime error to get back from the failing
+    /// function to an outer scope.
+    pub fn unwind_to(&mut self, depth: usize) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+            self.stats.release_scope(&self.variables);
+            self.variables = frame.caller_variables;
+        }
+    }
+}
+
+/// A function call in progress.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The name of the called function.
+    pub function: String,
+    /// The caller's variables, restored when the call returns.
+    pub caller_variables: HashMap<String, String>,
 }
 
 /// Represents an expression in the language.
@@ -22,6 +319,22 @@ pub enum Expr {
     Call(String, Vec<Expr>),
     /// A binary operation (e.g., addition, comparison).
     Binary(Box<Expr>, Op, Box<Expr>),
+    /// A chain of two or more comparisons, such as `0 <= x < 10`.
+    ///
+    /// It holds if every comparison holds, like `0 <= x` and `x < 10`, but
+    /// each operand is evaluated at most once, left to right, and evaluation
+    /// stops at the first comparison that fails.
+    Chain(Box<Expr>, Vec<(Op, Expr)>),
+    /// A `trace(expr)` call, which prints the expression's source text and
+    /// value to stderr, tagged with where it is, and returns the value.
+    Trace {
+        expr: Box<Expr>,
+        /// The expression as written.
+        source: String,
+        /// The line and column of the `trace` call.
+        line: usize,
+        column: usize,
+    },
 }
 
 /// Represents a statement in the language.
@@ -51,11 +364,80 @@ pub enum Stmt {
         name: String,
         params: Vec<String>,
         body: Vec<Stmt>,
+        /// Text of the `///` comments written directly above the definition.
+        doc: Option<String>,
+        /// The cache size given by a `@memo` annotation, if the function has one.
+        memo: Option<usize>,
     },
     /// Calls a function as a statement.
     Call(String, Vec<Expr>),
+    /// Evaluates an expression for its side effects, as `trace(x)` does on a
+    /// line by itself.
+    Expr(Expr),
     /// Returns a value from a function.
     Return(Expr),
+    /// Imports a module's functions under a namespace (`import utils as u`).
+    Import {
+        module: String,
+        alias: Option<String>,
+    },
+    /// Imports selected functions of a module by name (`from utils import parse`).
+    FromImport {
+        module: String,
+        names: Vec<String>,
+    },
+    /// Runs the first arm whose pattern matches the value of `subject`.
+    Match {
+        subject: Expr,
+        arms: Vec<MatchArm>,
+    },
+    /// A block timed by `dash bench` (`bench "label" { ... }`). Other runs
+    /// execute it once, like a plain block.
+    Bench {
+        label: String,
+        body: Vec<Stmt>,
+    },
+    /// A block included only when `flag` is defined (`@if(debug) { ... }`).
+    /// `apply_defines` replaces these with the chosen branch before a
+    /// program runs.
+    Cfg {
+        flag: String,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+    },
+    /// A use of a macro registered with `Context::define_macro`
+    /// (`retry(3) { ... }`), expanded when it runs.
+    Macro {
+        name: String,
+        args: Vec<Expr>,
+        body: Vec<Stmt>,
+    },
+}
+
+/// A single arm of a `match` statement.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    /// Alternative patterns (`1 | 2 | 3`); the arm applies if any of them matches.
+    pub patterns: Vec<Pattern>,
+    /// An optional `if` guard that must also hold for the arm to be taken.
+    pub guard: Option<Expr>,
+    /// Statements executed when the arm is taken.
+    pub body: Vec<Stmt>,
+    /// Location of the arm's patterns in the source.
+    pub span: Span,
+}
+
+/// Patterns usable in `match` arms.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// Matches a specific integer.
+    Int(i64),
+    /// Matches a specific string.
+    Str(String),
+    /// Matches any value and binds it to a variable.
+    Bind(String),
+    /// Matches any value (`_`).
+    Wildcard,
 }
 
 /// Supported binary operators.
@@ -64,7 +446,10 @@ pub enum Op {
     Add,
     Sub,
     Mul,
+    /// Integer division rounding toward zero (`-7 / 2` is `-3`).
     Div,
+    /// Integer division rounding down (`-7 // 2` is `-4`).
+    FloorDiv,
     Greater,
     Less,
     GreaterEq,
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::json::{quote, Reader};
+
+/// Settings and results for timing `bench` blocks, used by `dash bench`.
+#[derive(Debug, Clone)]
+pub struct Benchmarks {
+    /// Untimed runs of each block before measuring, to warm caches.
+    pub warmup: usize,
+    /// Timed runs of each block.
+    pub iterations: usize,
+    /// One entry per block run, in the order they ran.
+    pub results: Vec<BenchResult>,
+}
+
+/// The timings of one `bench` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchResult {
+    pub label: String,
+    /// How long each timed run took.
+    pub samples: Vec<Duration>,
+}
+
+impl Benchmarks {
+    pub fn new(warmup: usize, iterations: usize) -> Self {
+        Benchmarks {
+            warmup,
+            iterations,
+            results: Vec::new(),
+        }
+    }
+
+    /// Runs `body` `warmup` times, then `iterations` more times while
+    /// timing each run, and records the timings under `label`.
+    pub fn run<R>(&mut self, label: &str, mut body: impl FnMut() -> R) {
+        for _ in 0..self.warmup {
+            body();
+        }
+        let samples = (0..self.iterations.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                body();
+                start.elapsed()
+            })
+            .collect();
+        self.results.push(BenchResult {
+            label: label.to_string(),
+            samples,
+        });
+    }
+
+    /// Renders the results as a baseline for a later `--baseline`
+    /// comparison: a JSON object keyed by label, with times in nanoseconds.
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .results
+            .iter()
+            .map(|r| {
+                format!(
+                    "{}:{{\"mean_ns\":{},\"median_ns\":{},\"stddev_ns\":{},\"runs\":{}}}",
+                    quote(&r.label),
+                    r.mean().as_nanos(),
+                    r.median().as_nanos(),
+                    r.stddev().as_nanos(),
+                    r.samples.len()
+                )
+            })
+            .collect();
+        format!("{{{}}}\n", entries.join(","))
+    }
+}
+
+impl BenchResult {
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let middle = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2
+        } else {
+            sorted[middle]
+        }
+    }
+
+    /// The population standard deviation of the samples.
+    pub fn stddev(&self) -> Duration {
+        let mean = self.mean().as_secs_f64();
+        let variance = self
+            .samples
+            .iter()
+            .map(|s| (s.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        Duration::from_secs_f64(variance.sqrt())
+    }
+
+    /// Describes the result on one line, with the change in mean time
+    /// against `baseline` if there is one.
+    pub fn summary(&self, baseline: Option<Duration>) -> String {
+        let mut line = format!(
+            "{}: mean {:.2?}, median {:.2?}, stddev {:.2?} ({} runs)",
+            self.label,
+            self.mean(),
+            self.median(),
+            self.stddev(),
+            self.samples.len()
+        );
+        if let Some(baseline) = baseline.filter(|b| !b.is_zero()) {
+            let change = (self.mean().as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0;
+            line.push_str(&format!(", {:+.1}% vs baseline {:.2?}", change, baseline));
+        }
+        line
+    }
+}
+
+/// Reads the mean times from a baseline written by `Benchmarks::to_json`.
+///
+/// # Returns
+/// The mean time of each label, or a message if the text isn't a baseline.
+pub fn parse_baseline(text: &str) -> Result<HashMap<String, Duration>, String> {
+    parse_means(text).map_err(|e| format!("Invalid baseline: {}", e))
+}
+
+/// Parses a baseline, with errors that don't yet say what was being read.
+fn parse_means(text: &str) -> Result<HashMap<String, Duration>, String> {
+    let mut reader = Reader::new(text);
+    let mut means = HashMap::new();
+    reader.expect('{')?;
+    if !reader.eat('}') {
+        loop {
+            let label = reader.string()?;
+            reader.expect(':')?;
+            reader.expect('{')?;
+            loop {
+                let key = reader.string()?;
+                reader.expect(':')?;
+                let value = reader.number()?;
+                if key == "mean_ns" {
+                    means.insert(label.clone(), Duration::from_nanos(value));
+                }
+                if !reader.eat(',') {
+                    break;
+                }
+            }
+            reader.expect('}')?;
+            if !reader.eat(',') {
+                break;
+            }
+        }
+        reader.expect('}')?;
+    }
+    Ok(means)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_and_baseline_round_trip() {
+        let ms = Duration::from_millis;
+        let mut benchmarks = Benchmarks::new(0, 1);
+        benchmarks.results.push(BenchResult {
+            label: "sort \"big\"".to_string(),
+            samples: vec![ms(4), ms(1), ms(3), ms(2)],
+        });
+        let result = &benchmarks.results[0];
+        assert_eq!(result.mean(), Duration::from_micros(2500));
+        assert_eq!(result.median(), Duration::from_micros(2500));
+        assert_eq!(result.stddev().as_micros(), 1118);
+        assert_eq!(
+            result.summary(Some(ms(2))),
+            "sort \"big\": mean 2.50ms, median 2.50ms, stddev 1.12ms (4 runs), +25.0% vs baseline 2.00ms"
+        );
+
+        let baseline = parse_baseline(&benchmarks.to_json()).unwrap();
+        assert_eq!(baseline["sort \"big\""], Duration::from_micros(2500));
+        assert!(parse_baseline("{\"x\": 1}").is_err());
+        assert!(parse_baseline(" { } ").unwrap().is_empty());
+    }
+}
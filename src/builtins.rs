@@ -0,0 +1,613 @@
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::ast::Context;
+use crate::cfg::apply_defines;
+use crate::collate::{self, CompareOptions};
+use crate::encoding;
+use crate::eval::{eval_expr, exec_stmt};
+use crate::events::run_events;
+use crate::include::read_include;
+use crate::interrupt;
+use crate::logging::LogLevel;
+use crate::parser::{parse, parse_expr};
+use crate::prompt::{read_input, Input};
+use crate::paths;
+use crate::random;
+use crate::shared::{Access, SharedTable};
+use crate::snapshot;
+use crate::store;
+use crate::temp::create_temp;
+use crate::units;
+use crate::terminal::{self, Progress};
+
+/// Names of the functions provided by the interpreter itself.
+///
+/// User-defined functions with the same name take precedence.
+pub const BUILTINS: &[&str] = &[
+    "log_debug",
+    "log_info",
+    "log_warn",
+    "log_error",
+    "repeat",
+    "pad_left",
+    "pad_right",
+    "set_timeout",
+    "set_interval",
+    "clear_timer",
+    "run_events",
+    "sleep",
+    "on_exit",
+    "on_interrupt",
+    "wait",
+    "include_str",
+    "eval",
+    "trace",
+    "hash",
+    "sha256",
+    "hex",
+    "base64_encode",
+    "base64_decode",
+    "secure_equals",
+    "uuid4",
+    "random_hex",
+    "nanoid",
+    "progress_start",
+    "progress_tick",
+    "term_clear",
+    "term_color",
+    "confirm",
+    "select",
+    "password",
+    "read_line",
+    "has_line",
+    "read_all_stdin",
+    "glob",
+    "path_join",
+    "basename",
+    "dirname",
+    "extension",
+    "absolute",
+    "temp_file",
+    "temp_dir",
+    "format_duration",
+    "parse_duration",
+    "format_bytes",
+    "parse_bytes",
+    "saturating_add",
+    "saturating_sub",
+    "saturating_mul",
+    "compare",
+    "diff",
+    "assert_eq",
+    "store_get",
+    "store_set",
+    "store_delete",
+    "shared_get",
+    "shared_set",
+    "shared_delete",
+];
+
+/// Predefined variables, readable in every scope unless a variable of the
+/// same name is set.
+pub const CONSTANTS: &[(&str, &str)] = &[
+    ("INT_MAX", "9223372036854775807"),
+    ("INT_MIN", "-9223372036854775808"),
+];
+
+/// Returns the value of a predefined variable, if `name` is one.
+pub fn constant(name: &str) -> Option<String> {
+    CONSTANTS
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Builtins whose result depends only on their arguments, which
+/// `ExprProgram` allows.
+pub const PURE_BUILTINS: &[&str] = &[
+    "repeat",
+    "pad_left",
+    "pad_right",
+    "hash",
+    "sha256",
+    "hex",
+    "base64_encode",
+    "base64_decode",
+    "secure_equals",
+    "format_duration",
+    "parse_duration",
+    "format_bytes",
+    "parse_bytes",
+    "saturating_add",
+    "saturating_sub",
+    "saturating_mul",
+    "compare",
+    "diff",
+    "assert_eq",
+];
+
+/// Returns `true` if `name` is a builtin function.
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+/// Calls a builtin function with already-evaluated arguments.
+///
+/// # Arguments
+/// * `name` - The builtin to call.
+/// * `args` - The argument values.
+/// * `ctx` - The mutable execution context.
+///
+/// # Returns
+/// The builtin's result as a string.
+pub fn call_builtin(name: &str, args: &[String], ctx: &mut Context) -> String {
+    match name {
+        "log_debug" => log(LogLevel::Debug, name, args, ctx),
+        "log_info" => log(LogLevel::Info, name, args, ctx),
+        "log_warn" => log(LogLevel::Warn, name, args, ctx),
+        "log_error" => log(LogLevel::Error, name, args, ctx),
+        _ if PURE_BUILTINS.contains(&name) => call_pure_builtin(name, args, ctx.string_limit),
+        "set_timeout" | "set_interval" => schedule(name, args, ctx),
+        "clear_timer" => {
+            expect_args(name, args, 1);
+            let id = int_arg(name, &args[0]) as u64;
+            if ctx.timers.cancel(id) { "1" } else { "0" }.to_string()
+        }
+        "sleep" => {
+            expect_args(name, args, 1);
+            let delay = Duration::from_millis(int_arg(name, &args[0]) as u64);
+            interrupt::sleep_until(Instant::now() + delay, ctx);
+            String::new()
+        }
+        "on_exit" | "on_interrupt" => {
+            expect_args(name, args, 1);
+            let hooks = if name == "on_exit" {
+                &mut ctx.exit_hooks
+            } else {
+                &mut ctx.interrupt_hooks
+            };
+            hooks.push(args[0].clone());
+            String::new()
+        }
+        // `trace(expr)` in source is parsed as `Expr::Trace`. Called by name,
+        // as from a timer, there is no source text to show.
+        "trace" => {
+            expect_args(name, args, 1);
+            eprintln!("{}", args[0]);
+            args[0].clone()
+        }
+        // `Interpreter` handles `wait` itself when it can suspend the script.
+        "wait" => panic!(
+            "wait() can only be used in scripts run step by step by an Interpreter, outside function bodies"
+        ),
+        // Scripts loaded from a file have these calls replaced by the file
+        // contents before they run; see `resolve_includes`.
+        "include_str" => {
+            expect_args(name, args, 1);
+            require_fs(name, ctx);
+            read_include(&ctx.base_dir, &args[0]).unwrap_or_else(|e| panic!("{}", e))
+        }
+        "eval" => {
+            expect_args(name, args, 1);
+            if !ctx.permissions.allow_eval {
+                panic!("eval() is not allowed here; the host must enable Permissions::allow_eval");
+            }
+            eval_source(&args[0], ctx)
+        }
+        "uuid4" => {
+            expect_args(name, args, 0);
+            random::uuid4(&mut ctx.rng)
+        }
+        "random_hex" => {
+            expect_args(name, args, 1);
+            random::random_hex(&mut ctx.rng, int_arg(name, &args[0]))
+        }
+        "nanoid" => {
+            expect_args(name, args, 1);
+            random::nanoid(&mut ctx.rng, int_arg(name, &args[0]))
+        }
+        "progress_start" => {
+            expect_args(name, args, 1);
+            let progress = Progress {
+                total: int_arg(name, &args[0]) as u64,
+                done: 0,
+            };
+            terminal::draw_progress(&progress);
+            ctx.progress = Some(progress);
+            String::new()
+        }
+        "progress_tick" => {
+            expect_args(name, args, 0);
+            let progress = ctx
+                .progress
+                .as_mut()
+                .unwrap_or_else(|| panic!("progress_tick() called before progress_start()"));
+            progress.done += 1;
+            terminal::draw_progress(progress);
+            String::new()
+        }
+        "term_clear" => {
+            expect_args(name, args, 0);
+            terminal::clear();
+            String::new()
+        }
+        "term_color" => {
+            expect_args(name, args, 2);
+            terminal::color(&args[0], &args[1]).unwrap_or_else(|| {
+                panic!("Unknown color '{}'; expected one of: {}", args[1], terminal::color_names())
+            })
+        }
+        "confirm" => {
+            expect_args(name, args, 1);
+            if read_input(ctx, |input| input.confirm(&args[0])) { "1" } else { "0" }.to_string()
+        }
+        "select" => {
+            if args.len() < 2 {
+                panic!("Function 'select' expected a message and at least 1 option");
+            }
+            read_input(ctx, |input| input.select(&args[0], &args[1..]))
+        }
+        "password" => {
+            expect_args(name, args, 1);
+            read_input(ctx, |input| input.password(&args[0]))
+        }
+        "read_line" => {
+            expect_args(name, args, 0);
+            read_input(ctx, Input::read_line).unwrap_or_default()
+        }
+        "has_line" => {
+            expect_args(name, args, 0);
+            if read_input(ctx, Input::has_line) { "1" } else { "0" }.to_string()
+        }
+        "read_all_stdin" => {
+            expect_args(name, args, 0);
+            read_input(ctx, Input::read_all)
+        }
+        "store_get" => {
+            if args.is_empty() || args.len() > 2 {
+                panic!("Function 'store_get' expects a key and optionally a default");
+            }
+            require_fs(name, ctx);
+            store::get(&args[0], ctx).unwrap_or_else(|| args.get(1).cloned().unwrap_or_default())
+        }
+        "store_set" => {
+            expect_args(name, args, 2);
+            require_fs(name, ctx);
+            store::set(&args[0], &args[1], ctx);
+            String::new()
+        }
+        "store_delete" => {
+            expect_args(name, args, 1);
+            require_fs(name, ctx);
+            if store::delete(&args[0], ctx) { "1" } else { "0" }.to_string()
+        }
+        "shared_get" => {
+            if args.len() < 2 || args.len() > 3 {
+                panic!("Function 'shared_get' expects a table, a key and optionally a default");
+            }
+            let value = shared_table(&args[0], false, ctx).get(&args[1]);
+            value.unwrap_or_else(|| args.get(2).cloned().unwrap_or_default())
+        }
+        "shared_set" => {
+            expect_args(name, args, 3);
+            shared_table(&args[0], true, ctx).insert(args[1].as_str(), args[2].as_str());
+            String::new()
+        }
+        "shared_delete" => {
+            expect_args(name, args, 2);
+            let removed = shared_table(&args[0], true, ctx).remove(&args[1]);
+            if removed.is_some() { "1" } else { "0" }.to_string()
+        }
+        "glob" => {
+            expect_args(name, args, 1);
+            require_fs(name, ctx);
+            let found: Vec<String> = paths::glob(&args[0])
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            found.join("\n")
+        }
+        "path_join" => {
+            if args.is_empty() {
+                panic!("Function 'path_join' expected at least 1 args, got 0");
+            }
+            let joined: PathBuf = args.iter().collect();
+            joined.display().to_string()
+        }
+        "basename" | "dirname" | "extension" => {
+            expect_args(name, args, 1);
+            let path = Path::new(&args[0]);
+            let part = match name {
+                "basename" => path.file_name().map(Path::new),
+                "dirname" => path.parent(),
+                _ => path.extension().map(Path::new),
+            };
+            part.map_or(String::new(), |part| part.display().to_string())
+        }
+        "absolute" => {
+            expect_args(name, args, 1);
+            require_fs(name, ctx);
+            std::path::absolute(&args[0])
+                .unwrap_or_else(|e| panic!("absolute() can't resolve '{}': {}", args[0], e))
+                .display()
+                .to_string()
+        }
+        "temp_file" | "temp_dir" => {
+            if args.len() > 1 {
+                panic!("Function '{}' expected at most 1 args, got {}", name, args.len());
+            }
+            require_fs(name, ctx);
+            let suffix = args.first().map_or("", String::as_str);
+            create_temp(name == "temp_dir", suffix, ctx).display().to_string()
+        }
+        "run_events" => {
+            expect_args(name, args, 0);
+            run_events(ctx);
+            String::new()
+        }
+        _ => panic!("Undefined function: {}", name),
+    }
+}
+
+/// Calls one of the `PURE_BUILTINS`, which need no context.
+pub(crate) fn call_pure_builtin(name: &str, args: &[String], string_limit: Option<usize>) -> String {
+    match name {
+        "repeat" => {
+            expect_args(name, args, 2);
+            let count = int_arg(name, &args[1]);
+            check_length(name, args[0].len().checked_mul(count), string_limit);
+            args[0].repeat(count)
+        }
+        "pad_left" | "pad_right" => pad(name, args, string_limit),
+        "hash" => {
+            expect_args(name, args, 1);
+            format!("{:016x}", encoding::fnv1a(args[0].as_bytes()))
+        }
+        "sha256" => {
+            expect_args(name, args, 1);
+            sha256(&args[0])
+        }
+        "hex" => {
+            expect_args(name, args, 1);
+            encoding::hex(args[0].as_bytes())
+        }
+        "base64_encode" => {
+            expect_args(name, args, 1);
+            encoding::base64_encode(args[0].as_bytes())
+        }
+        "base64_decode" => {
+            expect_args(name, args, 1);
+            let bytes = encoding::base64_decode(&args[0])
+                .unwrap_or_else(|| panic!("base64_decode() got invalid base64: '{}'", args[0]));
+            String::from_utf8(bytes).unwrap_or_else(|_| panic!("base64_decode() result is not valid UTF-8 text"))
+        }
+        "saturating_add" | "saturating_sub" | "saturating_mul" => {
+            expect_args(name, args, 2);
+            let (a, b) = (number_arg(name, &args[0]), number_arg(name, &args[1]));
+            match name {
+                "saturating_add" => a.saturating_add(b),
+                "saturating_sub" => a.saturating_sub(b),
+                _ => a.saturating_mul(b),
+            }
+            .to_string()
+        }
+        "compare" => {
+            if args.len() < 2 {
+                panic!("Function 'compare' expects two strings, then any options");
+            }
+            let mut options = CompareOptions::default();
+            for option in &args[2..] {
+                match option.as_str() {
+                    "ignore_case" => options.ignore_case = true,
+                    "numeric" => options.numeric = true,
+                    _ => panic!("Unknown compare option '{}'; expected ignore_case or numeric", option),
+                }
+            }
+            match collate::compare(&args[0], &args[1], options) {
+                Ordering::Less => "-1",
+                Ordering::Equal => "0",
+                Ordering::Greater => "1",
+            }
+            .to_string()
+        }
+        "diff" => {
+            expect_args(name, args, 2);
+            snapshot::diff(&args[0], &args[1]).unwrap_or_default()
+        }
+        "assert_eq" => {
+            expect_args(name, args, 2);
+            let (left, right) = (&args[0], &args[1]);
+            if left == right {
+                String::new()
+            } else if left.contains('\n') || right.contains('\n') {
+                let diff = snapshot::diff(left, right).unwrap();
+                panic!("assert_eq failed (- left, + right):\n{}", diff.trim_end());
+            } else {
+                panic!("assert_eq failed: left is \"{}\", right is \"{}\"", left, right);
+            }
+        }
+        "secure_equals" => {
+            expect_args(name, args, 2);
+            let equal = encoding::constant_time_eq(args[0].as_bytes(), args[1].as_bytes());
+            if equal { "1" } else { "0" }.to_string()
+        }
+        "format_duration" => {
+            expect_args(name, args, 1);
+            let ms = args[0]
+                .parse()
+                .unwrap_or_else(|_| panic!("Function 'format_duration' expected milliseconds, got '{}'", args[0]));
+            units::format_duration(ms)
+        }
+        "parse_duration" => {
+            expect_args(name, args, 1);
+            units::parse_duration(&args[0])
+                .unwrap_or_else(|| panic!("Invalid duration '{}'; expected e.g. 1h30m, 90s or 250ms", args[0]))
+                .to_string()
+        }
+        "format_bytes" => {
+            expect_args(name, args, 1);
+            units::format_bytes(int_arg(name, &args[0]) as u64)
+        }
+        "parse_bytes" => {
+            expect_args(name, args, 1);
+            units::parse_bytes(&args[0])
+                .unwrap_or_else(|| panic!("Invalid size '{}'; expected e.g. 512, 10KB or 4.2 MiB", args[0]))
+                .to_string()
+        }
+        _ => panic!("Undefined function: {}", name),
+    }
+}
+
+/// Implements `sha256(s)`, returning the digest in hexadecimal.
+#[cfg(feature = "crypto")]
+fn sha256(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    encoding::hex(&Sha256::digest(text.as_bytes()))
+}
+
+#[cfg(not(feature = "crypto"))]
+fn sha256(_text: &str) -> String {
+    panic!("sha256() is not available; dash-lang was built without the 'crypto' feature")
+}
+
+/// Implements `log_<level>(msg, key1, value1, ...)`.
+///
+/// The message may be followed by key/value pairs, which are written as
+/// structured fields of the record.
+fn log(level: LogLevel, name: &str, args: &[String], ctx: &mut Context) -> String {
+    if args.len().is_multiple_of(2) {
+        panic!(
+            "Function '{}' expects a message followed by key/value pairs, got {} args",
+            name,
+            args.len()
+        );
+    }
+    let fields: Vec<(&str, &str)> = args[1..]
+        .chunks(2)
+        .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+        .collect();
+    ctx.logger.log(level, &args[0], &fields);
+    String::new()
+}
+
+/// Implements `pad_left(s, width, fill)` and `pad_right(s, width, fill)`.
+///
+/// `s` is padded with `fill` until it is `width` characters long; strings
+/// that are already long enough are returned unchanged. Widths count
+/// characters, not bytes, so non-ASCII text lines up in columns.
+fn pad(name: &str, args: &[String], string_limit: Option<usize>) -> String {
+    expect_args(name, args, 3);
+    let width = int_arg(name, &args[1]);
+    let mut fill = args[2].chars();
+    let (Some(fill), None) = (fill.next(), fill.next()) else {
+        panic!("Function '{}' expects a single fill character, got '{}'", name, args[2]);
+    };
+    check_length(name, width.checked_mul(fill.len_utf8()), string_limit);
+    let missing = width.saturating_sub(args[0].chars().count());
+    let padding: String = std::iter::repeat_n(fill, missing).collect();
+    if name == "pad_left" {
+        padding + &args[0]
+    } else {
+        args[0].clone() + &padding
+    }
+}
+
+/// Implements `set_timeout(f, ms, args...)` and `set_interval(f, ms, args...)`.
+///
+/// Functions are passed by name. Any further arguments are passed to the
+/// function when the timer fires. Returns the timer id used by `clear_timer`.
+fn schedule(name: &str, args: &[String], ctx: &mut Context) -> String {
+    if args.len() < 2 {
+        panic!(
+            "Function '{}' expects a function name and a delay in milliseconds",
+            name
+        );
+    }
+    let delay = Duration::from_millis(int_arg(name, &args[1]) as u64);
+    let repeat = name == "set_interval";
+    ctx.timers
+        .schedule(&args[0], &args[2..], delay, repeat)
+        .to_string()
+}
+
+/// Implements `eval(code)`.
+///
+/// Code that is a single expression is evaluated and its value returned.
+/// Anything else runs as statements in the current scope and returns an
+/// empty string; the variables and functions it defines remain afterwards.
+fn eval_source(code: &str, ctx: &mut Context) -> String {
+    if let Ok(expr) = parse_expr(code) {
+        return eval_expr(&expr, ctx);
+    }
+    match parse(code) {
+        Ok(program) => {
+            // Unlike a script, the code stops at its first error whatever
+            // the error mode.
+            for stmt in &apply_defines(program, &ctx.defines) {
+                exec_stmt(stmt, ctx);
+            }
+            String::new()
+        }
+        Err(e) => panic!("Parse error in eval(): {}", e),
+    }
+}
+
+/// Returns the shared table mounted as `name`, panicking if there is none
+/// or if `write` is asked of a read-only mount.
+fn shared_table<'a>(name: &str, write: bool, ctx: &'a Context) -> &'a SharedTable {
+    match ctx.shared_tables.get(name) {
+        Some((_, Access::ReadOnly)) if write => panic!("Shared table '{}' is mounted read-only", name),
+        Some((table, _)) => table,
+        None => panic!("No shared table is mounted as '{}'", name),
+    }
+}
+
+/// Panics unless the host allows builtins that read the filesystem.
+fn require_fs(name: &str, ctx: &Context) {
+    if !ctx.permissions.allow_fs {
+        panic!("{}() is not allowed here; the host must enable Permissions::allow_fs", name);
+    }
+}
+
+/// Fails if a builtin would build a string of `len` bytes, `None` meaning
+/// more than fits in memory, that is over `string_limit`
+/// (`Context::string_limit`).
+fn check_length(name: &str, len: Option<usize>, string_limit: Option<usize>) {
+    match (len, string_limit) {
+        (Some(len), Some(limit)) if len > limit => panic!("String length limit of {} exceeded", limit),
+        (None, Some(limit)) => panic!("String length limit of {} exceeded", limit),
+        (None, None) => panic!("{}() would build a string too long to hold", name),
+        _ => {}
+    }
+}
+
+/// Parses an integer argument, which may be negative.
+fn number_arg(name: &str, value: &str) -> i64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("Function '{}' expects an integer, got '{}'", name, value))
+}
+
+/// Panics unless a builtin was called with exactly `count` arguments.
+fn expect_args(name: &str, args: &[String], count: usize) {
+    if args.len() != count {
+        panic!(
+            "Function '{}' expected {} args, got {}",
+            name,
+            count,
+            args.len()
+        );
+    }
+}
+
+/// Parses a builtin argument that must be a non-negative integer.
+fn int_arg(name: &str, value: &str) -> usize {
+    value.parse().unwrap_or_else(|_| {
+        panic!(
+            "Function '{}' expects a non-negative integer, got '{}'",
+            name, value
+        )
+    })
+}
@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use crate::ast::Stmt;
+
+/// Replaces every `@if(flag) { ... } else { ... }` block in a program with
+/// the statements of the branch that `defines` selects, so blocks for flags
+/// that aren't defined are gone before the program runs.
+///
+/// Blocks are replaced wherever they appear, including inside functions.
+///
+/// # Arguments
+/// * `stmts` - The parsed program.
+/// * `defines` - The flags that are defined, such as those given with `--define`.
+///
+/// # Returns
+/// The program without `@if` blocks.
+pub fn apply_defines(stmts: Vec<Stmt>, defines: &HashSet<String>) -> Vec<Stmt> {
+    let mut result = Vec::with_capacity(stmts.len());
+    for stmt in stmts {
+        match stmt {
+            Stmt::Cfg {
+                flag,
+                then_branch,
+                else_branch,
+            } => {
+                let branch = if defines.contains(&flag) {
+                    then_branch
+                } else {
+                    else_branch.unwrap_or_default()
+                };
+                result.extend(apply_defines(branch, defines));
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => result.push(Stmt::If {
+                condition,
+                then_branch: apply_defines(then_branch, defines),
+                else_branch: else_branch.map(|branch| apply_defines(branch, defines)),
+            }),
+            Stmt::While { condition, body } => result.push(Stmt::While {
+                condition,
+                body: apply_defines(body, defines),
+            }),
+            Stmt::Fn {
+                name,
+                params,
+                body,
+                doc,
+                memo,
+            } => result.push(Stmt::Fn {
+                name,
+                params,
+                body: apply_defines(body, defines),
+                doc,
+                memo,
+            }),
+            Stmt::Bench { label, body } => result.push(Stmt::Bench {
+                label,
+                body: apply_defines(body, defines),
+            }),
+            Stmt::Macro { name, args, body } => result.push(Stmt::Macro {
+                name,
+                args,
+                body: apply_defines(body, defines),
+            }),
+            Stmt::Match { subject, mut arms } => {
+                for arm in &mut arms {
+                    arm.body = apply_defines(std::mem::take(&mut arm.body), defines);
+                }
+                result.push(Stmt::Match { subject, arms });
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::ContextBuilder;
+    use crate::parser::run_with_context;
+
+    const SOURCE: &str = "let mode = \"release\"\n@if(debug) {\n  let mode = \"debug\"\n}\nfn check() {\n  @if(debug) {\n    return 1\n  } else {\n    return 0\n  }\n}\nlet checked = check()\n";
+
+    #[test]
+    fn test_defines_select_blocks() {
+        let mut ctx = ContextBuilder::new().build();
+        run_with_context(SOURCE, &mut ctx);
+        assert_eq!(ctx.variables["mode"], "release");
+        assert_eq!(ctx.variables["checked"], "0");
+        assert_eq!(ctx.functions["check"].1.len(), 1);
+
+        let mut ctx = ContextBuilder::new().define("debug").build();
+        run_with_context(SOURCE, &mut ctx);
+        assert_eq!(ctx.variables["mode"], "debug");
+        assert_eq!(ctx.variables["checked"], "1");
+    }
+}
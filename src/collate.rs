@@ -0,0 +1,82 @@
+use std::cmp::Ordering;
+
+/// How `compare` orders two strings. With every option off, strings are
+/// compared by Unicode code point, which gives the same order on every
+/// platform and in every locale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompareOptions {
+    /// Compare letters regardless of case, so `apple` comes before `Banana`.
+    pub ignore_case: bool,
+    /// Compare runs of digits by their value, so `file2` comes before `file10`.
+    pub numeric: bool,
+}
+
+/// Compares two strings as configured by `options`.
+///
+/// Strings that differ only in ways the options ignore, such as case, or
+/// leading zeros in a number, are ordered by code point, so the order is
+/// always total.
+pub fn compare(a: &str, b: &str, options: CompareOptions) -> Ordering {
+    compare_loosely(a, b, options).then_with(|| a.cmp(b))
+}
+
+/// Compares two strings, treating them as equal where the options say to.
+fn compare_loosely(a: &str, b: &str, options: CompareOptions) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        let (Some(&x), Some(&y)) = (a.peek(), b.peek()) else {
+            return a.peek().is_some().cmp(&b.peek().is_some());
+        };
+        if options.numeric && x.is_ascii_digit() && y.is_ascii_digit() {
+            let x = take_digits(&mut a);
+            let y = take_digits(&mut b);
+            let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+            let order = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+            if order != Ordering::Equal {
+                return order;
+            }
+            continue;
+        }
+        let order = if options.ignore_case {
+            x.to_lowercase().cmp(y.to_lowercase())
+        } else {
+            x.cmp(&y)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+        a.next();
+        b.next();
+    }
+}
+
+/// Takes a run of ASCII digits from the front of `chars`.
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_options() {
+        let plain = CompareOptions::default();
+        assert_eq!(compare("file10", "file2", plain), Ordering::Less);
+        assert_eq!(compare("Banana", "apple", plain), Ordering::Less);
+
+        let natural = CompareOptions {
+            ignore_case: true,
+            numeric: true,
+        };
+        let mut names = ["file10.txt", "File2.txt", "file2.txt", "file02.txt", "apple"];
+        names.sort_by(|a, b| compare(a, b, natural));
+        assert_eq!(names, ["apple", "File2.txt", "file02.txt", "file2.txt", "file10.txt"]);
+        assert_eq!(compare("a", "a", natural), Ordering::Equal);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::ast::Context;
+use crate::builtins::{BUILTINS, CONSTANTS};
+use crate::refactor::KEYWORDS;
+
+/// What a completion inserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompletionKind {
+    /// A variable or parameter in scope at the cursor.
+    Variable,
+    /// A function defined in the source or in the context.
+    Function,
+    /// A named constant, such as `INT_MAX`.
+    Constant,
+    /// A builtin function.
+    Builtin,
+    /// A keyword of the language.
+    Keyword,
+}
+
+/// A name that can be inserted at the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// A function's signature, such as `add(a, b)`, or a constant's value.
+    pub detail: Option<String>,
+    /// How many arguments a function takes, if known.
+    pub arity: Option<usize>,
+}
+
+/// Returns the names that can complete the word ending at `offset`.
+///
+/// The source doesn't need to parse, since it usually won't while it is
+/// being typed. Variables are those assigned or passed as parameters
+/// earlier in the enclosing function body (or at the top level), along with
+/// the variables of `ctx`; functions are those defined anywhere in the
+/// source or in `ctx`, such as a REPL session's. A word containing a `.`
+/// completes the functions of an imported namespace. Nothing is offered
+/// inside a string or where a new name is being defined, after `let` or `fn`.
+///
+/// # Arguments
+/// * `source` - The text being edited.
+/// * `offset` - The byte offset of the cursor.
+/// * `ctx` - The context the code will run in.
+///
+/// # Returns
+/// The completions starting with the word before the cursor, sorted by
+/// label. A name is listed once, as the first of the kinds above it matches.
+pub fn complete(source: &str, offset: usize, ctx: &Context) -> Vec<Completion> {
+    let offset = offset.min(source.len());
+    let tokens = tokenize(source);
+    let mut cursor = Cursor::default();
+    let mut functions = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if let (Token::Word(_, "fn"), Some(Token::Word(_, name))) = (token, tokens.get(i + 1)) {
+            let params = parameters(&tokens[i + 2..]);
+            functions.push((name.to_string(), params));
+        }
+        if token.end() < offset {
+            cursor.advance(token);
+        } else if token.start() < offset {
+            // The cursor is inside this token.
+            if let Token::Str(..) = token {
+                return Vec::new();
+            }
+        }
+    }
+
+    let prefix = word_before(source, offset);
+    if matches!(cursor.previous, Some("let" | "fn")) {
+        return Vec::new();
+    }
+
+    let mut completions = Vec::new();
+    let mut add = |label: &str, kind, detail: Option<String>, arity| {
+        if label.starts_with(prefix) {
+            completions.push(Completion {
+                label: label.to_string(),
+                kind,
+                detail,
+                arity,
+            });
+        }
+    };
+    for name in cursor.variables().chain(ctx.variables.keys().map(String::as_str)) {
+        add(name, CompletionKind::Variable, None, None);
+    }
+    let context_functions = ctx.functions.iter().map(|(name, (params, _))| (name.clone(), params.clone()));
+    for (name, params) in functions.into_iter().chain(context_functions) {
+        let signature = format!("{}({})", name, params.join(", "));
+        add(&name, CompletionKind::Function, Some(signature), Some(params.len()));
+    }
+    for (name, value) in CONSTANTS {
+        add(name, CompletionKind::Constant, Some(value.to_string()), None);
+    }
+    for name in BUILTINS.iter().filter(|name| !ctx.disabled_builtins.contains(**name)) {
+        add(name, CompletionKind::Builtin, None, None);
+    }
+    for keyword in KEYWORDS {
+        add(keyword, CompletionKind::Keyword, None, None);
+    }
+
+    completions.sort_by(|a, b| a.label.cmp(&b.label).then(a.kind.cmp(&b.kind)));
+    completions.dedup_by(|later, first| later.label == first.label);
+    completions
+}
+
+/// A token of the source, with its byte offset.
+enum Token<'a> {
+    /// A name, keyword or number.
+    Word(usize, &'a str),
+    /// A string literal, which may be unterminated.
+    Str(usize, usize),
+    /// Any other character.
+    Punct(usize, char),
+}
+
+impl Token<'_> {
+    fn start(&self) -> usize {
+        match *self {
+            Token::Word(start, _) | Token::Str(start, _) | Token::Punct(start, _) => start,
+        }
+    }
+
+    fn end(&self) -> usize {
+        match *self {
+            Token::Word(start, word) => start + word.len(),
+            Token::Str(_, end) => end,
+            Token::Punct(start, c) => start + c.len_utf8(),
+        }
+    }
+}
+
+/// Splits source into words, strings and punctuation, skipping whitespace.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '"' {
+            let end = chars
+                .find(|&(_, c)| c == '"')
+                .map_or(source.len(), |(i, _)| i + 1);
+            tokens.push(Token::Str(start, end));
+        } else if is_word_char(c) {
+            let mut end = start + 1;
+            while let Some(&(i, c)) = chars.peek().filter(|(_, c)| is_word_char(*c)) {
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Word(start, &source[start..end]));
+        } else {
+            tokens.push(Token::Punct(start, c));
+        }
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Returns the word ending at `offset`: the text being completed.
+fn word_before(source: &str, offset: usize) -> &str {
+    let start = source[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word_char(c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    &source[start..offset]
+}
+
+/// Reads the parameter names of a function from the tokens after its name.
+fn parameters<'a>(tokens: &[Token<'a>]) -> Vec<String> {
+    if !matches!(tokens.first(), Some(Token::Punct(_, '('))) {
+        return Vec::new();
+    }
+    tokens[1..]
+        .iter()
+        .take_while(|token| !matches!(token, Token::Punct(_, ')' | '{')))
+        .filter_map(|token| match token {
+            Token::Word(_, name) => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The scopes open at the cursor, built up one token at a time.
+#[derive(Default)]
+struct Cursor<'a> {
+    /// The top-level scope, then one entry per enclosing function body:
+    /// the brace depth its body started at, and its variables.
+    scopes: Vec<(usize, Vec<&'a str>)>,
+    depth: usize,
+    /// Parameters of a function whose body hasn't started yet.
+    pending: Option<Vec<&'a str>>,
+    /// The last word before the cursor.
+    previous: Option<&'a str>,
+}
+
+impl<'a> Cursor<'a> {
+    fn advance(&mut self, token: &Token<'a>) {
+        if self.scopes.is_empty() {
+            self.scopes.push((0, Vec::new()));
+        }
+        match *token {
+            Token::Word(_, word) => {
+                if self.previous == Some("let") {
+                    self.scopes.last_mut().unwrap().1.push(word);
+                } else if let Some(params) = self.pending.as_mut().filter(|_| self.previous != Some("fn")) {
+                    params.push(word);
+                }
+                if word == "fn" {
+                    self.pending = Some(Vec::new());
+                }
+                self.previous = Some(word);
+            }
+            Token::Punct(_, '{') => {
+                self.depth += 1;
+                if let Some(params) = self.pending.take() {
+                    self.scopes.push((self.depth, params));
+                }
+                self.previous = None;
+            }
+            Token::Punct(_, '}') => {
+                if self.scopes.len() > 1 && self.scopes.last().unwrap().0 == self.depth {
+                    self.scopes.pop();
+                }
+                self.depth = self.depth.saturating_sub(1);
+                self.previous = None;
+            }
+            _ => self.previous = None,
+        }
+    }
+
+    /// The variables visible in the innermost scope.
+    fn variables(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.scopes.last().into_iter().flat_map(|(_, variables)| variables.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(source: &str, ctx: &Context) -> Vec<(String, CompletionKind)> {
+        let offset = source.find('|').unwrap();
+        let source = source.replace('|', "");
+        complete(&source, offset, ctx)
+            .into_iter()
+            .map(|c| (c.label, c.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_completions_follow_scope() {
+        let ctx = Context::default();
+        let source = "let total = 1\nfn add(amount, b) {\n  let acc = amount + b\n  print(a|)\n}\nfn apply(f) {\n  return f\n}\n";
+        assert_eq!(
+            labels(source, &ctx),
+            [
+                ("absolute".to_string(), CompletionKind::Builtin),
+                ("acc".to_string(), CompletionKind::Variable),
+                ("add".to_string(), CompletionKind::Function),
+                ("amount".to_string(), CompletionKind::Variable),
+                ("apply".to_string(), CompletionKind::Function),
+                ("as".to_string(), CompletionKind::Keyword),
+                ("assert_eq".to_string(), CompletionKind::Builtin),
+            ]
+        );
+        // The function's variables aren't visible after its body.
+        let after = labels("fn f(abc) {\n  let abd = 1\n}\nprint(ab|", &ctx);
+        assert!(after.iter().all(|(_, kind)| *kind != CompletionKind::Variable));
+
+        let add = complete("fn add(a, b) {\n}\nad", 19, &ctx);
+        assert_eq!(add[0].detail.as_deref(), Some("add(a, b)"));
+        assert_eq!(add[0].arity, Some(2));
+    }
+
+    #[test]
+    fn test_context_names_and_no_completion_spots() {
+        let mut ctx = Context::default();
+        ctx.variables.insert("session_var".to_string(), "1".to_string());
+        ctx.functions.insert("u.parse".to_string(), (vec!["x".to_string()], Default::default()));
+        assert_eq!(labels("print(sess|)", &ctx), [("session_var".to_string(), CompletionKind::Variable)]);
+        assert_eq!(labels("u.p|", &ctx), [("u.parse".to_string(), CompletionKind::Function)]);
+        assert_eq!(labels("INT_M|", &ctx)[0].1, CompletionKind::Constant);
+        assert!(labels("let sess|", &ctx).is_empty());
+        assert!(labels("print(\"sess|\")", &ctx).is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use crate::json;
+
+/// A location in the source text, tracked both as byte offsets and as a
+/// 1-based line/column pair for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Byte offset where the span starts.
+    pub start: usize,
+    /// Byte offset just past the end of the span.
+    pub end: usize,
+    /// 1-based line of `start`.
+    pub line: usize,
+    /// 1-based column of `start`.
+    pub column: usize,
+}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span<'_>) -> Self {
+        let (line, column) = span.start_pos().line_col();
+        Span {
+            start: span.start(),
+            end: span.end(),
+            line,
+            column,
+        }
+    }
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A message produced by a static check, pointing at the offending source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// A suggested edit that resolves the problem, applied by `dash fix`.
+    pub fix: Option<Fix>,
+}
+
+/// A machine-applicable edit: replace the text in `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Diagnostic {
+    /// Creates a warning diagnostic.
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            span,
+            fix: None,
+        }
+    }
+
+    /// Creates an error diagnostic.
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            fix: None,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Attaches a fix that replaces the text in `span` with `replacement`.
+    pub fn with_fix(mut self, span: Span, replacement: impl Into<String>) -> Self {
+        self.fix = Some(Fix {
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+}
+
+/// Applies the fixes attached to `diagnostics` to `source`.
+///
+/// Fixes are applied in source order; a fix overlapping one already applied
+/// is skipped, so running the checks again may find more to fix.
+///
+/// # Returns
+/// The fixed source and the number of fixes applied.
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> (String, usize) {
+    let mut fixes: Vec<&Fix> = diagnostics.iter().filter_map(|d| d.fix.as_ref()).collect();
+    fixes.sort_by_key(|fix| (fix.span.start, fix.span.end));
+
+    let mut fixed = String::new();
+    let mut copied = 0;
+    let mut applied = 0;
+    for fix in fixes {
+        if fix.span.start < copied {
+            continue;
+        }
+        fixed.push_str(&source[copied..fix.span.start]);
+        fixed.push_str(&fix.replacement);
+        copied = fix.span.end;
+        applied += 1;
+    }
+    fixed.push_str(&source[copied..]);
+    (fixed, applied)
+}
+
+/// Returns the known name closest to `name`, if any is close enough to
+/// plausibly be what was meant.
+///
+/// # Arguments
+/// * `name` - The unknown name.
+/// * `candidates` - The names that exist.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Counts the single-character insertions, deletions and substitutions
+/// needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl Diagnostic {
+    /// Encodes the diagnostic as a single-line JSON object with `code`,
+    /// `severity`, `message`, `span` and `fix` fields, for editors and CI
+    /// tools. `code` names the check that produced it, such as `syntax` or
+    /// `runtime`. A span with line 0 isn't a real location and is encoded as
+    /// `null`.
+    pub fn to_json(&self, code: &str) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let fix = match &self.fix {
+            Some(fix) => format!(
+                "{{\"span\":{},\"replacement\":{}}}",
+                span_json(&fix.span),
+                json::quote(&fix.replacement)
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"code\":{},\"severity\":\"{}\",\"message\":{},\"span\":{},\"fix\":{}}}",
+            json::quote(code),
+            severity,
+            json::quote(&self.message),
+            span_json(&self.span),
+            fix
+        )
+    }
+}
+
+/// Encodes a span as a JSON object, or `null` if it has no line.
+fn span_json(span: &Span) -> String {
+    if span.line == 0 {
+        return "null".to_string();
+    }
+    format!(
+        "{{\"start\":{},\"end\":{},\"line\":{},\"column\":{}}}",
+        span.start, span.end, span.line, span.column
+    )
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{} at {}:{}: {}",
+            label, self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_and_apply_fixes() {
+        assert_eq!(edit_distance("prnt", "print"), 1);
+        assert_eq!(suggest("prnt", ["print", "repeat"]), Some("print"));
+        assert_eq!(suggest("xyz", ["print"]), None);
+
+        let at = |start, end| Span {
+            start,
+            end,
+            ..Span::default()
+        };
+        let diagnostics = [
+            Diagnostic::warning("call", at(9, 13)).with_fix(at(9, 13), "print"),
+            Diagnostic::error("let", at(6, 8)).with_fix(at(6, 8), "="),
+            Diagnostic::warning("overlap", at(7, 8)).with_fix(at(7, 8), "?"),
+            Diagnostic::warning("no fix", at(0, 3)),
+        ];
+        let (fixed, applied) = apply_fixes("let a == prnt(1)", &diagnostics);
+        assert_eq!(fixed, "let a = print(1)");
+        assert_eq!(applied, 2);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let span = Span {
+            start: 4,
+            end: 8,
+            line: 1,
+            column: 5,
+        };
+        let diagnostic = Diagnostic::warning("unknown function `prnt`", span).with_fix(span, "print");
+        assert_eq!(
+            diagnostic.to_json("lint"),
+            r#"{"code":"lint","severity":"warning","message":"unknown function `prnt`","span":{"start":4,"end":8,"line":1,"column":5},"fix":{"span":{"start":4,"end":8,"line":1,"column":5},"replacement":"print"}}"#
+        );
+        let runtime = Diagnostic::error("Undefined variable: \"x\"", Span::default());
+        assert_eq!(
+            runtime.to_json("runtime"),
+            r#"{"code":"runtime","severity":"error","message":"Undefined variable: \"x\"","span":null,"fix":null}"#
+        );
+    }
+}
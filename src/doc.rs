@@ -0,0 +1,120 @@
+use crate::ast::Stmt;
+
+/// A documented top-level item of a script.
+struct DocItem<'a> {
+    signature: String,
+    doc: Option<&'a str>,
+}
+
+/// Collects the top-level function definitions of a program, in source order.
+fn collect_items(program: &[Stmt]) -> Vec<DocItem<'_>> {
+    program
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Fn {
+                name, params, doc, ..
+            } => Some(DocItem {
+                signature: format!("fn {}({})", name, params.join(", ")),
+                doc: doc.as_deref(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Renders Markdown documentation for the top-level functions of a program.
+///
+/// Each function gets a heading with its signature followed by the text of
+/// its `///` comments, which is copied through as Markdown.
+///
+/// # Arguments
+/// * `title` - The page title, usually the script's file name.
+/// * `program` - The parsed program.
+///
+/// # Returns
+/// The Markdown document.
+pub fn render_markdown(title: &str, program: &[Stmt]) -> String {
+    let mut out = format!("# {}\n", title);
+    for item in collect_items(program) {
+        out.push_str(&format!("\n## `{}`\n", item.signature));
+        if let Some(doc) = item.doc {
+            out.push_str(&format!("\n{}\n", doc));
+        }
+    }
+    out
+}
+
+/// Renders a standalone HTML page documenting the top-level functions of a program.
+///
+/// Blank lines in a doc comment separate paragraphs.
+///
+/// # Arguments
+/// * `title` - The page title, usually the script's file name.
+/// * `program` - The parsed program.
+///
+/// # Returns
+/// The HTML document.
+pub fn render_html(title: &str, program: &[Stmt]) -> String {
+    let title = escape_html(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        title
+    );
+    for item in collect_items(program) {
+        out.push_str(&format!(
+            "<h2><code>{}</code></h2>\n",
+            escape_html(&item.signature)
+        ));
+        for paragraph in item.doc.unwrap_or("").split("\n\n") {
+            if !paragraph.trim().is_empty() {
+                out.push_str(&format!("<p>{}</p>\n", escape_html(paragraph.trim())));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Escapes the characters that are special in HTML text.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    const SOURCE: &str = r#"
+/// Adds two numbers.
+///
+/// Both arguments must be integers.
+fn add(a, b) {
+  return a + b
+}
+
+fn noop() {
+}
+"#;
+
+    #[test]
+    fn test_render_markdown() {
+        let program = parse(SOURCE).unwrap();
+        assert_eq!(
+            render_markdown("math.dash", &program),
+            "# math.dash\n\n## `fn add(a, b)`\n\nAdds two numbers.\n\nBoth arguments must be integers.\n\n## `fn noop()`\n"
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        let program = parse(SOURCE).unwrap();
+        let html = render_html("<math>", &program);
+        assert!(html.contains("<h1>&lt;math&gt;</h1>"));
+        assert!(html.contains("<p>Adds two numbers.</p>\n<p>Both arguments must be integers.</p>"));
+        assert!(html.contains("<h2><code>fn noop()</code></h2>"));
+    }
+}
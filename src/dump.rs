@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::ast::{Context, Stmt};
+use crate::json::quote;
+
+/// A snapshot of a context's variables and functions, made by `Context::dump`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDump {
+    /// Every scope with variables, outermost (top level) first and the
+    /// currently running function last.
+    pub scopes: Vec<ScopeDump>,
+    /// Defined functions, sorted by name.
+    pub functions: Vec<FunctionDump>,
+    /// Total size in bytes of all variable values.
+    pub value_bytes: usize,
+}
+
+/// The variables of one scope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeDump {
+    /// The function the scope belongs to, or `None` for the top level.
+    pub function: Option<String>,
+    /// The variables, sorted by name.
+    pub variables: Vec<VariableDump>,
+}
+
+/// A variable and the size of its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableDump {
+    pub name: String,
+    pub value: String,
+    /// Size of the value in bytes.
+    pub bytes: usize,
+}
+
+/// A function's signature and the size of its body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDump {
+    pub name: String,
+    pub params: Vec<String>,
+    /// Number of statements in the body, including nested ones.
+    pub statements: usize,
+}
+
+impl Context {
+    /// Takes a snapshot of every variable in scope, including those of the
+    /// callers of a function that is running, and of every defined function.
+    pub fn dump(&self) -> StateDump {
+        let mut owners = vec![None];
+        owners.extend(self.frames.iter().map(|frame| Some(frame.function.clone())));
+        let tables = self
+            .frames
+            .iter()
+            .map(|frame| &frame.caller_variables)
+            .chain(std::iter::once(&self.variables));
+        let scopes: Vec<ScopeDump> = owners
+            .into_iter()
+            .zip(tables)
+            .map(|(function, variables)| ScopeDump {
+                function,
+                variables: dump_variables(variables),
+            })
+            .collect();
+
+        let mut functions: Vec<FunctionDump> = self
+            .functions
+            .iter()
+            .map(|(name, (params, body))| FunctionDump {
+                name: name.clone(),
+                params: params.clone(),
+                statements: count_statements(body),
+            })
+            .collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let value_bytes = scopes
+            .iter()
+            .flat_map(|scope| &scope.variables)
+            .map(|variable| variable.bytes)
+            .sum();
+        StateDump {
+            scopes,
+            functions,
+            value_bytes,
+        }
+    }
+}
+
+impl StateDump {
+    /// Renders the snapshot as a JSON object.
+    pub fn to_json(&self) -> String {
+        let scopes: Vec<String> = self
+            .scopes
+            .iter()
+            .map(|scope| {
+                let function = scope.function.as_deref().map_or("null".to_string(), quote);
+                let variables: Vec<String> = scope
+                    .variables
+                    .iter()
+                    .map(|v| {
+                        format!(
+                            "{{\"name\":{},\"value\":{},\"bytes\":{}}}",
+                            quote(&v.name),
+                            quote(&v.value),
+                            v.bytes
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"function\":{},\"variables\":[{}]}}",
+                    function,
+                    variables.join(",")
+                )
+            })
+            .collect();
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|f| {
+                let params: Vec<String> = f.params.iter().map(|p| quote(p)).collect();
+                format!(
+                    "{{\"name\":{},\"params\":[{}],\"statements\":{}}}",
+                    quote(&f.name),
+                    params.join(","),
+                    f.statements
+                )
+            })
+            .collect();
+        format!(
+            "{{\"scopes\":[{}],\"functions\":[{}],\"value_bytes\":{}}}\n",
+            scopes.join(","),
+            functions.join(","),
+            self.value_bytes
+        )
+    }
+}
+
+/// Lists the variables of a scope, sorted by name.
+fn dump_variables(variables: &HashMap<String, String>) -> Vec<VariableDump> {
+    let mut dumped: Vec<VariableDump> = variables
+        .iter()
+        .map(|(name, value)| VariableDump {
+            name: name.clone(),
+            value: value.clone(),
+            bytes: value.len(),
+        })
+        .collect();
+    dumped.sort_by(|a, b| a.name.cmp(&b.name));
+    dumped
+}
+
+/// Counts statements, including those nested in blocks.
+fn count_statements(stmts: &[Stmt]) -> usize {
+    stmts
+        .iter()
+        .map(|stmt| {
+            1 + match stmt {
+                Stmt::If {
+                    then_branch,
+                    else_branch,
+                    ..
+                }
+                | Stmt::Cfg {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => count_statements(then_branch) + else_branch.as_deref().map_or(0, count_statements),
+                Stmt::While { body, .. }
+                | Stmt::Fn { body, .. }
+                | Stmt::Bench { body, .. }
+                | Stmt::Macro { body, .. } => count_statements(body),
+                Stmt::Match { arms, .. } => arms.iter().map(|arm| count_statements(&arm.body)).sum(),
+                _ => 0,
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Context;
+    use crate::eval::catch_runtime_error;
+    use crate::parser::run_with_context;
+
+    #[test]
+    fn test_dump_includes_caller_scopes() {
+        let mut ctx = Context::default();
+        let source = "fn fail(x) {\n  let y = \"abcd\"\n  if x {\n    return missing\n  }\n}\nlet count = 12\nfail(1)\n";
+        catch_runtime_error(|| run_with_context(source, &mut ctx)).unwrap_err();
+
+        let dump = ctx.dump();
+        assert_eq!(dump.scopes.len(), 2);
+        assert_eq!(dump.scopes[0].function, None);
+        assert_eq!(dump.scopes[1].function.as_deref(), Some("fail"));
+        assert_eq!(dump.functions[0].statements, 3);
+        assert_eq!(dump.value_bytes, 2 + 1 + 4);
+        assert_eq!(
+            dump.to_json(),
+            "{\"scopes\":[{\"function\":null,\"variables\":[{\"name\":\"count\",\"value\":\"12\",\"bytes\":2}]},\
+             {\"function\":\"fail\",\"variables\":[{\"name\":\"x\",\"value\":\"1\",\"bytes\":1},{\"name\":\"y\",\"value\":\"abcd\",\"bytes\":4}]}],\
+             \"functions\":[{\"name\":\"fail\",\"params\":[\"x\"],\"statements\":3}],\"value_bytes\":7}\n"
+        );
+    }
+}
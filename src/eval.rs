@@ -1,10 +1,23 @@
-use crate::ast::{Expr, Stmt, Context, LoopControl, Op};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::ast::{Expr, Stmt, Context, ErrorMode, Frame, LoopControl, MatchArm, Op, Pattern};
+use crate::builtins::{call_builtin, constant, is_builtin};
+use crate::format::op_str;
+use crate::interrupt;
+use crate::memo::MemoCache;
+use crate::temp::remove_temp_paths;
+use crate::timing;
+use crate::module;
 
 /// Evaluates an expression within the given context and returns its result as a string.
 ///
 /// Supports literals, variables, binary operations, and function calls.
 /// Binary operations are evaluated as integer arithmetic or comparisons.
-/// Function calls are executed with a new local context.
+/// Function calls are executed with a fresh set of local variables.
 ///
 /// # Arguments
 /// * `expr` - The expression to evaluate.
@@ -12,7 +25,8 @@ use crate::ast::{Expr, Stmt, Context, LoopControl, Op};
 ///
 /// # Returns
 /// A string representing the result of the evaluated expression.
-pub fn eval_expr(expr: &Expr, ctx: &Context) -> String {
+pub fn eval_expr(expr: &Expr, ctx: &mut Context) -> String {
+    ctx.stats.allocations += 1;
     match expr {
         Expr::Int(i) => i.to_string(),
         Expr::Str(s) => s.clone(),
@@ -20,54 +34,38 @@ pub fn eval_expr(expr: &Expr, ctx: &Context) -> String {
             .variables
             .get(name)
             .cloned()
+            .or_else(|| constant(name))
             .unwrap_or_else(|| panic!("Undefined variable: {}", name)),
         Expr::Binary(left, op, right) => {
             let l = eval_expr(left, ctx).parse::<i64>().unwrap();
             let r = eval_expr(right, ctx).parse::<i64>().unwrap();
-            let result = match op {
-                Op::Add => l + r,
-                Op::Sub => l - r,
-                Op::Mul => l * r,
-                Op::Div => l / r,
-                Op::Greater => (l > r) as i64,
-                Op::Less => (l < r) as i64,
-                Op::GreaterEq => (l >= r) as i64,
-                Op::LessEq => (l <= r) as i64,
-                Op::Equal => (l == r) as i64,
-                Op::NotEqual => (l != r) as i64,
-            };
-            result.to_string()
-        }
-        Expr::Call(name, args) => {
-            let (params, body) = ctx
-                .functions
-                .get(name)
-                .unwrap_or_else(|| panic!("Undefined function: {}", name))
-                .clone();
-
-            if params.len() != args.len() {
-                panic!(
-                    "Function '{}' expected {} args, got {}",
-                    name,
-                    params.len(),
-                    args.len()
-                );
-            }
-
-            let mut local_ctx = Context::default();
-            for (param, arg) in params.iter().zip(args.iter()) {
-                let value = eval_expr(arg, ctx);
-                local_ctx.variables.insert(param.clone(), value);
-            }
-
-            for stmt in body {
-                match exec_stmt(&stmt, &mut local_ctx) {
-                    LoopControl::Return(val) => return val,
-                    LoopControl::None => continue,
-                    _ => panic!("Unexpected control flow in function"),
+            apply_op(l, op, r).to_string()
+        }
+        Expr::Chain(first, rest) => {
+            let mut l = eval_expr(first, ctx).parse::<i64>().unwrap();
+            for (op, right) in rest {
+                let r = eval_expr(right, ctx).parse::<i64>().unwrap();
+                if apply_op(l, op, r) == 0 {
+                    return "0".to_string();
                 }
+                l = r;
+            }
+            "1".to_string()
+        }
+        Expr::Call(name, args) => call_function(name, args, ctx),
+        Expr::Trace {
+            expr,
+            source,
+            line,
+            column,
+        } => {
+            // A user-defined `trace` takes precedence, as for other builtins.
+            if ctx.functions.contains_key("trace") {
+                return call_function("trace", std::slice::from_ref(expr.as_ref()), ctx);
             }
-            "".to_string()
+            let value = eval_expr(expr, ctx);
+            eprintln!("[{}:{}] {} = {}", line, column, source, value);
+            value
         }
     }
 }
@@ -84,14 +82,28 @@ pub fn eval_expr(expr: &Expr, ctx: &Context) -> String {
 /// # Returns
 /// A `LoopControl` value indicating control flow status (e.g., break, continue, return).
 pub fn exec_stmt(stmt: &Stmt, ctx: &mut Context) -> LoopControl {
+    ctx.stats.statements += 1;
+    check_interrupt(ctx);
+    if let Some(limit) = ctx.statement_limit.filter(|limit| ctx.stats.statements > *limit) {
+        panic!("Statement limit of {} exceeded", limit);
+    }
+    if ctx.line_times.is_some() {
+        return timing::timed(stmt, ctx, run_stmt);
+    }
+    run_stmt(stmt, ctx)
+}
+
+/// Executes a statement once `exec_stmt` has counted it.
+fn run_stmt(stmt: &Stmt, ctx: &mut Context) -> LoopControl {
     match stmt {
         Stmt::Print(expr) => {
-            println!("{}", eval_expr(expr, ctx));
+            let value = eval_expr(expr, ctx);
+            let _ = writeln!(ctx.output.sink, "{}", value);
             LoopControl::None
         }
         Stmt::Let(name, expr) => {
             let value = eval_expr(expr, ctx);
-            ctx.variables.insert(name.clone(), value);
+            ctx.set_variable(name, value);
             LoopControl::None
         }
         Stmt::Break => LoopControl::Break,
@@ -102,20 +114,13 @@ pub fn exec_stmt(stmt: &Stmt, ctx: &mut Context) -> LoopControl {
             else_branch,
         } => {
             let cond_value = eval_expr(condition, ctx);
-            let is_true = cond_value != "0" && cond_value != "" && cond_value != "false";
             let fallback = Vec::new();
-            let branch = if is_true {
+            let branch = if is_truthy(&cond_value) {
                 then_branch
             } else {
                 else_branch.as_ref().unwrap_or(&fallback)
             };
-            for stmt in branch {
-                match exec_stmt(stmt, ctx) {
-                    LoopControl::None => continue,
-                    control => return control,
-                }
-            }
-            LoopControl::None
+            exec_block(branch, ctx)
         }
         Stmt::While { condition, body } => {
             while eval_expr(condition, ctx) != "0" {
@@ -130,49 +135,634 @@ pub fn exec_stmt(stmt: &Stmt, ctx: &mut Context) -> LoopControl {
             }
             LoopControl::None
         }
-        Stmt::Fn { name, params, body } => {
+        Stmt::Fn {
+            name,
+            params,
+            body,
+            memo,
+            ..
+        } => {
             ctx.functions
-                .insert(name.clone(), (params.clone(), body.to_vec()));
+                .insert(name.clone(), (params.clone(), Rc::new(body.to_vec())));
+            match memo {
+                Some(limit) => ctx.memo.insert(name.clone(), MemoCache::new(*limit)),
+                None => ctx.memo.remove(name),
+            };
             LoopControl::None
         }
         Stmt::Call(name, args) => {
-            let (params, body) = ctx.functions.get(name).unwrap().clone();
-            let mut local_ctx = Context::default();
-            for (param, arg) in params.iter().zip(args.iter()) {
-                let value = eval_expr(arg, ctx);
-                local_ctx.variables.insert(param.clone(), value);
-            }
-            for stmt in body {
-                exec_stmt(&stmt, &mut local_ctx);
-            }
+            call_function(name, args, ctx);
+            LoopControl::None
+        }
+        Stmt::Expr(expr) => {
+            eval_expr(expr, ctx);
             LoopControl::None
         }
         Stmt::Return(expr) => {
             let value = eval_expr(expr, ctx);
             LoopControl::Return(value)
         }
+        Stmt::Import { module, alias } => {
+            module::import_namespace(module, alias.as_deref(), ctx);
+            LoopControl::None
+        }
+        Stmt::FromImport { module, names } => {
+            module::import_names(module, names, ctx);
+            LoopControl::None
+        }
+        Stmt::Macro { name, args, body } => {
+            let expansion = match ctx.macros.get(name) {
+                Some(definition) => definition.expand(name, args, body),
+                None => panic!("Undefined macro: {}", name),
+            };
+            exec_block(&expansion, ctx)
+        }
+        Stmt::Bench { label, body } => match ctx.benchmarks.take() {
+            Some(mut benchmarks) => {
+                // Put the results back even if the block fails.
+                let result = catch_runtime_error(|| benchmarks.run(label, || exec_block(body, ctx)));
+                ctx.benchmarks = Some(benchmarks);
+                if let Err(message) = result {
+                    panic!("{}", message);
+                }
+                LoopControl::None
+            }
+            None => exec_block(body, ctx),
+        },
+        // Only reached by programs that didn't go through `apply_defines`.
+        Stmt::Cfg {
+            flag,
+            then_branch,
+            else_branch,
+        } => {
+            let fallback = Vec::new();
+            let branch = if ctx.defines.contains(flag) {
+                then_branch
+            } else {
+                else_branch.as_ref().unwrap_or(&fallback)
+            };
+            exec_block(branch, ctx)
+        }
+        Stmt::Match { subject, arms } => match select_arm(subject, arms, ctx) {
+            Some(arm) => exec_block(&arm.body, ctx),
+            None => LoopControl::None,
+        },
+    }
+}
+
+/// Calls a function and returns its result.
+///
+/// User-defined functions take precedence over builtins of the same name.
+/// Functions defined with `@memo` return a cached result when called again
+/// with the same argument values.
+///
+/// Arguments are evaluated in the caller's scope. The caller's variables are
+/// saved in a new `Frame` on `ctx.frames` while the body runs with only the
+/// parameters in scope, and restored once the function returns. Functions
+/// remain visible, so functions can call each other and recurse.
+///
+/// # Arguments
+/// * `name` - The name of the function to call.
+/// * `args` - The argument expressions.
+/// * `ctx` - The mutable execution context.
+///
+/// # Returns
+/// The value passed to `return`, or an empty string if the function has none.
+fn call_function(name: &str, args: &[Expr], ctx: &mut Context) -> String {
+    ctx.stats.calls += 1;
+    if !ctx.functions.contains_key(name)
+        && is_builtin(name)
+        && !ctx.disabled_builtins.contains(name)
+    {
+        let values: Vec<String> = args.iter().map(|arg| eval_expr(arg, ctx)).collect();
+        return call_builtin(name, &values, ctx);
+    }
+
+    let (params, body) = ctx
+        .functions
+        .get(name)
+        .unwrap_or_else(|| panic!("Undefined function: {}", name))
+        .clone();
+
+    if params.len() != args.len() {
+        panic!(
+            "Function '{}' expected {} args, got {}",
+            name,
+            params.len(),
+            args.len()
+        );
+    }
+
+    if let Some(limit) = ctx.depth_limit.filter(|limit| ctx.frames.len() >= *limit) {
+        panic!("Call depth limit of {} exceeded", limit);
+    }
+
+    let values: Vec<String> = args.iter().map(|arg| eval_expr(arg, ctx)).collect();
+    let memo_key = match ctx.memo.get(name) {
+        Some(cache) => match cache.get(&values) {
+            Some(result) => return result.clone(),
+            None => Some(values.clone()),
+        },
+        None => None,
+    };
+    let caller_variables = std::mem::take(&mut ctx.variables);
+    ctx.frames.push(Frame {
+        function: name.to_string(),
+        caller_variables,
+    });
+    for (param, value) in params.iter().zip(values) {
+        ctx.set_variable(param, value);
+    }
+
+    let mut result = String::new();
+    for stmt in body.iter() {
+        match exec_stmt(stmt, ctx) {
+            LoopControl::Return(val) => {
+                result = val;
+                break;
+            }
+            LoopControl::None => continue,
+            _ => panic!("Unexpected control flow in function"),
+        }
+    }
+
+    ctx.unwind_to(ctx.frames.len() - 1);
+    if let (Some(key), Some(cache)) = (memo_key, ctx.memo.get_mut(name)) {
+        cache.insert(key, result.clone());
+    }
+    result
+}
+
+/// Stops the script if it has been interrupted, through `Context::interrupt`
+/// or Ctrl-C, or its deadline has passed. Checked before every statement and
+/// while builtins wait.
+pub(crate) fn check_interrupt(ctx: &mut Context) {
+    if ctx.interrupt.swap(false, Ordering::SeqCst) || interrupt::take_signal() {
+        handle_interrupt(ctx);
+    }
+    let now = Instant::now();
+    if ctx.deadline.is_some_and(|deadline| now >= deadline) {
+        // Hooks that are still running when the grace period ends are
+        // interrupted in turn, with no hooks left to run.
+        ctx.deadline = Some(now + INTERRUPT_GRACE);
+        handle_interrupt(ctx);
+    }
+}
+
+/// How long the `on_interrupt` hooks may run once `Context::deadline` has
+/// passed.
+pub const INTERRUPT_GRACE: Duration = Duration::from_secs(1);
+
+/// Runs the `on_interrupt` hooks, then stops the script with an
+/// `INTERRUPTED` runtime error.
+fn handle_interrupt(ctx: &mut Context) -> ! {
+    for hook in std::mem::take(&mut ctx.interrupt_hooks).into_iter().rev() {
+        call_function(&hook, &[], ctx);
+    }
+    panic!("{}", interrupt::INTERRUPTED);
+}
+
+/// Runs the functions registered with `on_exit`, most recently registered
+/// first. Each hook runs once; hooks registered by other hooks run too.
+/// Temporary files and directories are deleted once the hooks are done, even
+/// if one of them fails.
+///
+/// `run` does this when the program finishes and the CLI does it however
+/// the script stops. Hosts using `run_with_context` decide when a script has
+/// terminated and call this themselves.
+pub fn run_exit_hooks(ctx: &mut Context) {
+    let result = catch_runtime_error(|| {
+        while let Some(hook) = ctx.exit_hooks.pop() {
+            call_function(&hook, &[], ctx);
+        }
+    });
+    remove_temp_paths(ctx);
+    if let Err(message) = result {
+        panic!("{}", message);
+    }
+}
+
+/// Executes the top-level statements of a program in order.
+///
+/// With `ErrorMode::Continue`, a statement failing with a runtime error is
+/// reported and skipped instead of stopping the program.
+///
+/// # Arguments
+/// * `program` - The statements to execute.
+/// * `ctx` - The mutable execution context.
+pub fn exec_program(program: &[Stmt], ctx: &mut Context) {
+    for stmt in program {
+        if ctx.error_mode == ErrorMode::Abort {
+            exec_stmt(stmt, ctx);
+            continue;
+        }
+        let depth = ctx.frames.len();
+        if let Err(message) = catch_runtime_error(|| exec_stmt(stmt, ctx)) {
+            if message == interrupt::INTERRUPTED {
+                panic!("{}", message);
+            }
+            ctx.unwind_to(depth);
+            match ctx.report_error {
+                Some(report) => report(&message),
+                None => eprintln!("Runtime error: {}", message),
+            }
+            ctx.errors.push(message);
+        }
+    }
+}
+
+/// Runs `f`, converting a runtime error into its message.
+///
+/// Runtime errors are raised as panics, so this is how hosts and the REPL keep
+/// going after a failing script. If the error happened inside a function call,
+/// `ctx.frames` is left as it was at the point of failure; use
+/// `Context::unwind_to` to get back to the caller's scope.
+///
+/// # Arguments
+/// * `f` - The code to run.
+///
+/// # Returns
+/// The result of `f`, or the error message if it panicked.
+pub fn catch_runtime_error<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown error".to_string()
+        }
+    })
+}
+
+/// Executes a block of statements, stopping early on any control flow signal.
+///
+/// # Arguments
+/// * `stmts` - The statements making up the block.
+/// * `ctx` - The mutable execution context.
+///
+/// # Returns
+/// The first non-`None` `LoopControl` produced by the block, or `LoopControl::None`.
+fn exec_block(stmts: &[Stmt], ctx: &mut Context) -> LoopControl {
+    for stmt in stmts {
+        match exec_stmt(stmt, ctx) {
+            LoopControl::None => continue,
+            control => return control,
+        }
+    }
+    LoopControl::None
+}
+
+/// Evaluates the subject of a `match` and finds the first arm that applies,
+/// binding the value for that arm if its pattern is an identifier.
+///
+/// The binding is visible to the arm's guard; if the guard fails, the
+/// variable is put back as it was before the arm was tried.
+///
+/// # Returns
+/// The arm to run, or `None` if no arm matches.
+pub(crate) fn select_arm<'a>(subject: &Expr, arms: &'a [MatchArm], ctx: &mut Context) -> Option<&'a MatchArm> {
+    let value = eval_expr(subject, ctx);
+    arms.iter().find(|arm| {
+        let Some(pattern) = arm.patterns.iter().find(|p| match_pattern(p, &value)) else {
+            return false;
+        };
+        let Pattern::Bind(name) = pattern else {
+            return guard_holds(arm, ctx);
+        };
+        let previous = ctx.variables.get(name).cloned();
+        ctx.set_variable(name, value.clone());
+        if guard_holds(arm, ctx) {
+            return true;
+        }
+        match previous {
+            Some(previous) => ctx.set_variable(name, previous),
+            None => {
+                if let Some(bound) = ctx.variables.remove(name) {
+                    ctx.stats.replace_value(bound.len(), 0);
+                }
+            }
+        }
+        false
+    })
+}
+
+/// Checks whether an arm's guard, if it has one, holds.
+fn guard_holds(arm: &MatchArm, ctx: &mut Context) -> bool {
+    arm.guard
+        .as_ref()
+        .is_none_or(|guard| is_truthy(&eval_expr(guard, ctx)))
+}
+
+/// Checks a value against a `match` pattern. An identifier matches any value.
+///
+/// # Arguments
+/// * `pattern` - The pattern to test.
+/// * `value` - The value being matched.
+///
+/// # Returns
+/// `true` if the value matches the pattern.
+fn match_pattern(pattern: &Pattern, value: &str) -> bool {
+    match pattern {
+        Pattern::Int(i) => value == i.to_string(),
+        Pattern::Str(s) => value == s,
+        Pattern::Bind(_) | Pattern::Wildcard => true,
+    }
+}
+
+/// Applies a binary operator to two integers. Comparisons produce 1 or 0.
+///
+/// Panics if `+`, `-` or `*` overflows; the `saturating_*` builtins are for
+/// arithmetic that should stop at the limits instead.
+pub(crate) fn apply_op(l: i64, op: &Op, r: i64) -> i64 {
+    let overflow = || -> i64 { panic!("Integer overflow in {} {} {}", l, op_str(op), r) };
+    if matches!(op, Op::Div | Op::FloorDiv) && r == 0 {
+        panic!("Division by zero in {} {} {}", l, op_str(op), r);
+    }
+    match op {
+        Op::Add => l.checked_add(r).unwrap_or_else(overflow),
+        Op::Sub => l.checked_sub(r).unwrap_or_else(overflow),
+        Op::Mul => l.checked_mul(r).unwrap_or_else(overflow),
+        Op::Div => l.checked_div(r).unwrap_or_else(overflow),
+        Op::FloorDiv => floor_div(l, r).unwrap_or_else(overflow),
+        Op::Greater => (l > r) as i64,
+        Op::Less => (l < r) as i64,
+        Op::GreaterEq => (l >= r) as i64,
+        Op::LessEq => (l <= r) as i64,
+        Op::Equal => (l == r) as i64,
+        Op::NotEqual => (l != r) as i64,
+    }
+}
+
+/// Divides two integers, rounding the quotient down rather than toward zero.
+/// Returns `None` if `r` is zero or the quotient overflows.
+fn floor_div(l: i64, r: i64) -> Option<i64> {
+    let quotient = l.checked_div(r)?;
+    if l.checked_rem(r)? != 0 && (l < 0) != (r < 0) {
+        Some(quotient - 1)
+    } else {
+        Some(quotient)
     }
 }
 
+/// Determines whether a value counts as true in a condition.
+///
+/// The values `"0"`, `""` and `"false"` are false; everything else is true.
+pub fn is_truthy(value: &str) -> bool {
+    value != "0" && !value.is_empty() && value != "false"
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{Expr, Op, Context};
+    use crate::ast::{Expr, Op, Context, MatchArm, Pattern};
+    use crate::diagnostic::Span;
 
     #[test]
     fn test_addition_expr() {
         let expr = Expr::Binary(Box::new(Expr::Int(2)), Op::Add, Box::new(Expr::Int(3)));
-        let ctx = Context::default();
-        let result = eval_expr(&expr, &ctx);
+        let mut ctx = Context::default();
+        let result = eval_expr(&expr, &mut ctx);
         assert_eq!(result, "5");
     }
 
+    #[test]
+    fn test_division_rounding() {
+        let mut ctx = Context::default();
+        let eval = |source: &str, ctx: &mut Context| {
+            eval_expr(&crate::parser::parse_expr(source).unwrap(), ctx)
+        };
+        assert_eq!(eval("-7 / 2", &mut ctx), "-3");
+        assert_eq!(eval("-7 // 2", &mut ctx), "-4");
+        assert_eq!(eval("7 // -2", &mut ctx), "-4");
+        assert_eq!(eval("-7 // -2", &mut ctx), "3");
+        assert_eq!(eval("6 // 3", &mut ctx), "2");
+    }
+
+    #[test]
+    fn test_comparison_chain() {
+        let mut ctx = Context::default();
+        crate::parser::run_with_context(
+            "fn seven() {\n  on_exit(\"called\")\n  return 7\n}\nlet inside = 0 <= seven() < 10\nlet outside = 10 <= seven() <= 20\nlet skipped = 3 > 5 > seven()\nlet ge = 7 >= 7\n",
+            &mut ctx,
+        );
+        assert_eq!(ctx.variables["inside"], "1");
+        assert_eq!(ctx.variables["outside"], "0");
+        assert_eq!(ctx.variables["skipped"], "0");
+        assert_eq!(ctx.variables["ge"], "1");
+        // `seven()` ran once per chain it was reached in.
+        assert_eq!(ctx.exit_hooks.len(), 2);
+    }
+
     #[test]
     fn test_variable_lookup() {
         let mut ctx = Context::default();
         ctx.variables.insert("x".to_string(), "42".to_string());
         let expr = Expr::Var("x".to_string());
-        let result = eval_expr(&expr, &ctx);
+        let result = eval_expr(&expr, &mut ctx);
         assert_eq!(result, "42");
     }
+
+    #[test]
+    fn test_match_alternation_and_guard() {
+        let mut ctx = Context::default();
+        let arm = |patterns, guard, value: &str| MatchArm {
+            patterns,
+            guard,
+            body: vec![Stmt::Let("out".to_string(), Expr::Str(value.to_string()))],
+            span: Span::default(),
+        };
+        let stmt = Stmt::Match {
+            subject: Expr::Var("n".to_string()),
+            arms: vec![
+                arm(vec![Pattern::Int(1), Pattern::Int(2)], None, "small"),
+                arm(
+                    vec![Pattern::Bind("x".to_string())],
+                    Some(Expr::Binary(
+                        Box::new(Expr::Var("x".to_string())),
+                        Op::Greater,
+                        Box::new(Expr::Int(10)),
+                    )),
+                    "big",
+                ),
+                arm(vec![Pattern::Wildcard], None, "other"),
+            ],
+        };
+
+        for (n, expected) in [("2", "small"), ("50", "big"), ("5", "other")] {
+            ctx.variables.insert("n".to_string(), n.to_string());
+            exec_stmt(&stmt, &mut ctx);
+            assert_eq!(ctx.variables["out"], expected);
+        }
+        // The guard failed for 5, so `x` still holds the 50 bound when the
+        // arm was taken.
+        assert_eq!(ctx.variables["x"], "50");
+
+        crate::parser::run_with_context(
+            "let x = 5\nmatch 3 {\n  x if x > 10 => {\n    let out = \"big\"\n  }\n  _ => {\n    let out = \"small\"\n  }\n}\n",
+            &mut ctx,
+        );
+        assert_eq!(ctx.variables["x"], "5");
+        assert_eq!(ctx.variables["out"], "small");
+    }
+
+    #[test]
+    fn test_functions_can_call_functions() {
+        let mut ctx = Context::default();
+        let double = Expr::Binary(
+            Box::new(Expr::Var("n".to_string())),
+            Op::Mul,
+            Box::new(Expr::Int(2)),
+        );
+        ctx.functions.insert(
+            "double".to_string(),
+            (vec!["n".to_string()], Rc::new(vec![Stmt::Return(double)])),
+        );
+        let quadruple = Expr::Call(
+            "double".to_string(),
+            vec![Expr::Call("double".to_string(), vec![Expr::Var("n".to_string())])],
+        );
+        ctx.functions.insert(
+            "quadruple".to_string(),
+            (vec!["n".to_string()], Rc::new(vec![Stmt::Return(quadruple)])),
+        );
+        ctx.variables.insert("n".to_string(), "1".to_string());
+
+        let call = Expr::Call("quadruple".to_string(), vec![Expr::Int(3)]);
+        assert_eq!(eval_expr(&call, &mut ctx), "12");
+        assert_eq!(ctx.variables["n"], "1");
+        assert!(ctx.frames.is_empty());
+    }
+
+    #[test]
+    fn test_runtime_error_keeps_failing_frame() {
+        let mut ctx = Context::default();
+        ctx.functions.insert(
+            "broken".to_string(),
+            (
+                vec!["a".to_string()],
+                Rc::new(vec![Stmt::Print(Expr::Var("missing".to_string()))]),
+            ),
+        );
+        ctx.variables.insert("top".to_string(), "1".to_string());
+
+        let call = Stmt::Call("broken".to_string(), vec![Expr::Int(7)]);
+        let err = catch_runtime_error(|| {
+            exec_stmt(&call, &mut ctx);
+        })
+        .unwrap_err();
+        assert_eq!(err, "Undefined variable: missing");
+        assert_eq!(ctx.frames[0].function, "broken");
+        assert_eq!(ctx.variables["a"], "7");
+
+        ctx.unwind_to(0);
+        assert_eq!(ctx.variables["top"], "1");
+    }
+
+    #[test]
+    fn test_stats_counters() {
+        let mut ctx = Context::default();
+        ctx.functions.insert(
+            "id".to_string(),
+            (vec!["s".to_string()], Rc::new(vec![Stmt::Return(Expr::Var("s".to_string()))])),
+        );
+        let call = Expr::Call("id".to_string(), vec![Expr::Str("abcd".to_string())]);
+        exec_stmt(&Stmt::Let("x".to_string(), call), &mut ctx);
+        exec_stmt(&Stmt::Let("x".to_string(), Expr::Str("ab".to_string())), &mut ctx);
+
+        let stats = ctx.stats();
+        assert_eq!(stats.statements, 3);
+        assert_eq!(stats.calls, 1);
+        assert_eq!(stats.allocations, 4);
+        assert_eq!(stats.value_bytes, 2);
+        assert_eq!(stats.peak_value_bytes, 4);
+
+        // A variable the host set directly can still be reassigned.
+        ctx.variables.insert("host".to_string(), "a long value".to_string());
+        exec_stmt(&Stmt::Let("host".to_string(), Expr::Str("x".to_string())), &mut ctx);
+        assert_eq!(ctx.variables["host"], "x");
+    }
+
+    #[test]
+    fn test_exit_and_interrupt_hooks() {
+        let program = crate::parser::parse(
+            "fn first() {\n  on_interrupt(\"marker\")\n}\nfn second() {\n  on_exit(\"first\")\n}\non_exit(\"first\")\non_exit(\"second\")\nlet x = 1\n",
+        )
+        .unwrap();
+        let mut ctx = Context::default();
+        ctx.interrupt.store(true, Ordering::SeqCst);
+        let err = catch_runtime_error(|| exec_program(&program, &mut ctx)).unwrap_err();
+        assert_eq!(err, interrupt::INTERRUPTED);
+        assert!(ctx.exit_hooks.is_empty());
+
+        exec_program(&program, &mut ctx);
+        assert_eq!(ctx.variables["x"], "1");
+        assert_eq!(ctx.exit_hooks, ["first", "second"]);
+        // `second` runs first and registers `first` again, so `first` leaves
+        // its marker twice.
+        run_exit_hooks(&mut ctx);
+        assert!(ctx.exit_hooks.is_empty());
+        assert_eq!(ctx.interrupt_hooks, ["marker", "marker"]);
+    }
+
+    #[test]
+    fn test_memo_caches_results() {
+        let source = "@memo(3)\nfn fib(n) {\n  if n < 2 {\n    return n\n  }\n  return fib(n - 1) + fib(n - 2)\n}\nlet x = fib(20)\n";
+        let mut ctx = Context::default();
+        exec_program(&crate::parser::parse(source).unwrap(), &mut ctx);
+        assert_eq!(ctx.variables["x"], "6765");
+        // Naively, fib(20) makes 21891 calls.
+        assert!(ctx.stats().calls < 100);
+        assert_eq!(ctx.memo["fib"].len(), 3);
+
+        exec_program(&crate::parser::parse("fn fib(n) {\n  return n\n}\n").unwrap(), &mut ctx);
+        assert!(!ctx.memo.contains_key("fib"));
+    }
+
+    #[test]
+    fn test_trace_keeps_source_and_location() {
+        let program = crate::parser::parse("let x = 4
+let y = trace( x  * 2) + 1
+").unwrap();
+        let Stmt::Let(_, Expr::Binary(traced, ..)) = &program[1] else {
+            panic!("expected an addition");
+        };
+        assert!(matches!(
+            traced.as_ref(),
+            Expr::Trace { source, line: 2, column: 9, .. } if source == "x  * 2"
+        ));
+        let mut ctx = Context::default();
+        exec_program(&program, &mut ctx);
+        assert_eq!(ctx.variables["y"], "9");
+    }
+
+    #[test]
+    fn test_trace_as_statement() {
+        let program = crate::parser::parse("let x = 1\ntrace(x)\nlet y = 2\n").unwrap();
+        assert!(matches!(&program[1], Stmt::Expr(Expr::Trace { source, .. }) if source == "x"));
+        let mut ctx = Context::default();
+        exec_program(&program, &mut ctx);
+        assert_eq!(ctx.variables["y"], "2");
+    }
+
+    #[test]
+    fn test_continue_mode_skips_failing_statements() {
+        let source = "let a = 1
+fn f(x) {
+  let local = x
+  return missing
+}
+let b = f(2)
+let c = 3
+";
+        let mut ctx = Context {
+            error_mode: ErrorMode::Continue,
+            ..Context::default()
+        };
+        exec_program(&crate::parser::parse(source).unwrap(), &mut ctx);
+        assert_eq!(ctx.errors, ["Undefined variable: missing"]);
+        assert_eq!(ctx.variables["c"], "3");
+        assert!(ctx.frames.is_empty());
+        assert!(!ctx.variables.contains_key("local"));
+    }
 }
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use crate::ast::{Context, Expr};
+use crate::eval::eval_expr;
+use crate::interrupt;
+
+/// Functions scheduled by `set_timeout` and `set_interval`, waiting to be run
+/// by the event loop.
+#[derive(Debug, Default)]
+pub struct Timers {
+    next_id: u64,
+    pending: Vec<Timer>,
+}
+
+/// A scheduled call.
+#[derive(Debug, Clone)]
+struct Timer {
+    id: u64,
+    due: Instant,
+    /// How often the timer repeats, or `None` for a one-shot timeout.
+    interval: Option<Duration>,
+    function: String,
+    args: Vec<String>,
+}
+
+impl Timers {
+    /// Schedules `function` to be called with `args` after `delay`, and every
+    /// `delay` after that if `repeat` is set.
+    ///
+    /// # Returns
+    /// The timer's id, which can be passed to `cancel`.
+    pub fn schedule(&mut self, function: &str, args: &[String], delay: Duration, repeat: bool) -> u64 {
+        self.next_id += 1;
+        self.pending.push(Timer {
+            id: self.next_id,
+            due: Instant::now() + delay,
+            interval: repeat.then_some(delay),
+            function: function.to_string(),
+            args: args.to_vec(),
+        });
+        self.next_id
+    }
+
+    /// Cancels a timer. Returns `false` if it had already fired or been cancelled.
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|timer| timer.id != id);
+        self.pending.len() != before
+    }
+
+    /// Returns the time at which the next timer is due, if any are pending.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|timer| timer.due).min()
+    }
+
+    /// Removes and returns the earliest timer if it is due at `now`,
+    /// rescheduling it first if it repeats.
+    fn pop_due(&mut self, now: Instant) -> Option<Timer> {
+        let index = (0..self.pending.len())
+            .filter(|&i| self.pending[i].due <= now)
+            .min_by_key(|&i| (self.pending[i].due, self.pending[i].id))?;
+        let timer = self.pending[index].clone();
+        match timer.interval {
+            Some(interval) => self.pending[index].due = timer.due + interval,
+            None => {
+                self.pending.remove(index);
+            }
+        }
+        Some(timer)
+    }
+}
+
+/// Runs the timers that are due now, in the order they became due.
+///
+/// Hosts with their own event loop call this whenever the deadline returned
+/// last time has passed, instead of using `run_events`.
+///
+/// # Returns
+/// When the next timer is due, or `None` if none are left.
+pub fn run_due_events(ctx: &mut Context) -> Option<Instant> {
+    let now = Instant::now();
+    while let Some(timer) = ctx.timers.pop_due(now) {
+        let args = timer.args.into_iter().map(Expr::Str).collect();
+        eval_expr(&Expr::Call(timer.function, args), ctx);
+    }
+    ctx.timers.next_due()
+}
+
+/// Runs scheduled functions until no timers are left, sleeping in between.
+///
+/// Callbacks may schedule further timers or cancel pending ones. With an
+/// interval that is never cancelled this does not return, unless the script
+/// is interrupted or reaches its deadline while waiting.
+pub fn run_events(ctx: &mut Context) {
+    while let Some(due) = run_due_events(ctx) {
+        interrupt::sleep_until(due, ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use crate::ast::Context;
+    use crate::parser::run_with_context;
+
+    /// A log sink that keeps everything written to it.
+    #[derive(Clone, Default)]
+    struct Capture(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_timeouts_and_intervals() {
+        let capture = Capture::default();
+        let mut ctx = Context::default();
+        ctx.logger.sink = Box::new(capture.clone());
+        run_with_context(
+            r#"
+fn tick() {
+  log_info("tick")
+}
+fn stop(timer) {
+  log_info("stop")
+  clear_timer(timer)
+}
+fn hello() {
+  log_info("hello")
+}
+let ticker = set_interval("tick", 10)
+set_timeout("stop", 35, ticker)
+set_timeout("hello", 15)
+run_events()
+"#,
+            &mut ctx,
+        );
+        let output = String::from_utf8(capture.0.borrow().clone()).unwrap();
+        assert_eq!(
+            output,
+            "[INFO] tick\n[INFO] hello\n[INFO] tick\n[INFO] tick\n[INFO] stop\n"
+        );
+        assert!(ctx.timers.next_due().is_none());
+    }
+}
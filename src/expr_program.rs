@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::ast::Expr;
+use crate::builtins::{call_pure_builtin, constant, PURE_BUILTINS};
+use crate::eval::{apply_op, catch_runtime_error};
+use crate::parser::parse_expr;
+
+/// A single expression compiled for repeated evaluation, such as a
+/// user-editable filter or rule.
+///
+/// Unlike a program, it can't run statements or call anything but the
+/// `PURE_BUILTINS`, so evaluating it has no side effects and needs no
+/// `Context`. It is `Send + Sync`, so one compiled expression can be shared
+/// by many threads.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use dash_lang::ExprProgram;
+///
+/// let filter = ExprProgram::compile("(size // 1024) > limit").unwrap();
+/// let bindings = HashMap::from([
+///     ("size".to_string(), "4096".to_string()),
+///     ("limit".to_string(), "2".to_string()),
+/// ]);
+/// assert_eq!(filter.evaluate(&bindings).unwrap(), "1");
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExprProgram {
+    expr: Expr,
+}
+
+impl ExprProgram {
+    /// Parses an expression, rejecting calls to anything other than the
+    /// `PURE_BUILTINS`.
+    ///
+    /// # Returns
+    /// The compiled expression, or a message describing why it isn't allowed.
+    pub fn compile(source: &str) -> Result<ExprProgram, String> {
+        let expr = parse_expr(source).map_err(|e| format!("Parse error: {}", e))?;
+        check_calls(&expr)?;
+        Ok(ExprProgram { expr })
+    }
+
+    /// Evaluates the expression with `bindings` as its variables.
+    ///
+    /// # Returns
+    /// The value, or the runtime error message, e.g. for a variable missing
+    /// from `bindings`.
+    pub fn evaluate(&self, bindings: &HashMap<String, String>) -> Result<String, String> {
+        catch_runtime_error(|| evaluate(&self.expr, bindings))
+    }
+}
+
+/// Fails if the expression calls a function that may have side effects.
+fn check_calls(expr: &Expr) -> Result<(), String> {
+    match expr {
+        Expr::Call(name, args) => {
+            if !PURE_BUILTINS.contains(&name.as_str()) {
+                return Err(format!("'{}' can't be called in an expression", name));
+            }
+            args.iter().try_for_each(check_calls)
+        }
+        Expr::Trace { .. } => Err("'trace' can't be called in an expression".to_string()),
+        Expr::Binary(left, _, right) => {
+            check_calls(left)?;
+            check_calls(right)
+        }
+        Expr::Chain(first, rest) => {
+            check_calls(first)?;
+            rest.iter().try_for_each(|(_, operand)| check_calls(operand))
+        }
+        Expr::Int(_) | Expr::Str(_) | Expr::Var(_) => Ok(()),
+    }
+}
+
+/// Evaluates an expression the way `eval_expr` would, panicking on errors.
+fn evaluate(expr: &Expr, bindings: &HashMap<String, String>) -> String {
+    match expr {
+        Expr::Int(i) => i.to_string(),
+        Expr::Str(s) => s.clone(),
+        Expr::Var(name) => bindings
+            .get(name)
+            .cloned()
+            .or_else(|| constant(name))
+            .unwrap_or_else(|| panic!("Undefined variable: {}", name)),
+        Expr::Binary(left, op, right) => {
+            let l = number(evaluate(left, bindings));
+            let r = number(evaluate(right, bindings));
+            apply_op(l, op, r).to_string()
+        }
+        Expr::Chain(first, rest) => {
+            let mut l = number(evaluate(first, bindings));
+            for (op, right) in rest {
+                let r = number(evaluate(right, bindings));
+                if apply_op(l, op, r) == 0 {
+                    return "0".to_string();
+                }
+                l = r;
+            }
+            "1".to_string()
+        }
+        Expr::Call(name, args) => {
+            let values: Vec<String> = args.iter().map(|arg| evaluate(arg, bindings)).collect();
+            call_pure_builtin(name, &values, None)
+        }
+        Expr::Trace { expr, .. } => evaluate(expr, bindings),
+    }
+}
+
+/// Parses an operand of an arithmetic or comparison operator.
+fn number(value: String) -> i64 {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("Expected a number, got '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_and_evaluate() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ExprProgram>();
+
+        let rule = ExprProgram::compile("pad_left(code, 3, \"0\")").unwrap();
+        let bindings = HashMap::from([("code".to_string(), "7".to_string())]);
+        assert_eq!(rule.evaluate(&bindings).unwrap(), "007");
+        assert_eq!(rule.evaluate(&HashMap::new()).unwrap_err(), "Undefined variable: code");
+
+        assert!(ExprProgram::compile("log_info(\"x\")").unwrap_err().contains("log_info"));
+        assert!(ExprProgram::compile("let x = 1").is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::ast::{Expr, MatchArm, Op, Pattern, Stmt};
+use crate::diagnostic::Span;
+use crate::memo::DEFAULT_MEMO_LIMIT;
+use crate::meta;
+use crate::parser::{parse, parse_with_ranges, ParseError};
+
+/// Where the formatter puts the opening brace of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BraceStyle {
+    /// `while x < 5 {`
+    #[default]
+    SameLine,
+    /// The brace goes on its own line, aligned with the statement.
+    NextLine,
+}
+
+/// Settings for `format_source` and `format_range`.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Spaces per nesting level.
+    pub indent_width: usize,
+    /// Placement of opening braces.
+    pub brace_style: BraceStyle,
+    /// Calls that would make a line longer than this get one argument per line.
+    pub max_line_length: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_width: 2,
+            brace_style: BraceStyle::SameLine,
+            max_line_length: 80,
+        }
+    }
+}
+
+/// Formats a whole program.
+///
+/// # Arguments
+/// * `source` - The program to format.
+/// * `options` - Layout settings.
+///
+/// # Returns
+/// The formatted program, or the error if it doesn't parse.
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    let mut formatter = Formatter::new(options);
+    let program = parse(source)?;
+    if let Some((_, end)) = meta::header(source) {
+        formatter.out.push_str(source[..end].trim_start());
+        formatter.out.push_str("\n\n");
+    }
+    formatter.stmts(&program);
+    Ok(formatter.out)
+}
+
+/// Formats only the top-level statements that overlap `span`, such as an
+/// editor selection or the statement a lint points at.
+///
+/// Text outside those statements, including blank lines between them, is
+/// left exactly as it was.
+///
+/// # Arguments
+/// * `source` - The whole program.
+/// * `span` - The byte range to format; only `start` and `end` are used.
+/// * `options` - Layout settings.
+///
+/// # Returns
+/// The program with the selected statements reformatted, or the error if it
+/// doesn't parse.
+pub fn format_range(source: &str, span: Span, options: &FormatOptions) -> Result<String, ParseError> {
+    let mut result = String::new();
+    let mut copied = 0;
+    for ((start, end), stmt) in parse_with_ranges(source)? {
+        if end < span.start || start > span.end {
+            continue;
+        }
+        // Statement ranges can include trailing whitespace; keep it as it was.
+        let end = start + source[start..end].trim_end().len();
+        let mut formatter = Formatter::new(options);
+        formatter.stmt(&stmt);
+        result.push_str(&source[copied..start]);
+        result.push_str(formatter.out.trim_end_matches('\n'));
+        copied = end;
+    }
+    result.push_str(&source[copied..]);
+    Ok(result)
+}
+
+/// Formats the first line of a statement, such as `while i < 3 {` for a
+/// loop, to identify it in reports.
+pub(crate) fn stmt_summary(stmt: &Stmt) -> String {
+    let options = FormatOptions {
+        max_line_length: usize::MAX,
+        ..FormatOptions::default()
+    };
+    let mut formatter = Formatter::new(&options);
+    formatter.stmt(stmt);
+    formatter.out.lines().find(|line| !line.starts_with("///")).unwrap_or_default().to_string()
+}
+
+/// Accumulates formatted output.
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    out: String,
+    depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(options: &'a FormatOptions) -> Self {
+        Formatter {
+            options,
+            out: String::new(),
+            depth: 0,
+        }
+    }
+
+    /// Writes one line at the current indentation.
+    fn line(&mut self, text: &str) {
+        let indent = " ".repeat(self.depth * self.options.indent_width);
+        for (i, part) in text.split('\n').enumerate() {
+            // Continuation lines of a wrapped call carry their own relative indentation.
+            if i > 0 || !part.is_empty() {
+                self.out.push_str(&indent);
+            }
+            self.out.push_str(part);
+            self.out.push('\n');
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Print(expr) => {
+                let text = self.call("print", std::slice::from_ref(expr), 0);
+                self.line(&text)
+            }
+            Stmt::Let(name, expr) => {
+                let prefix = format!("let {} = ", name);
+                let value = self.call_or_expr(expr, prefix.len());
+                self.line(&(prefix + &value))
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.conditional(&format!("if {}", expr(condition)), then_branch, else_branch.as_deref()),
+            Stmt::While { condition, body } => {
+                self.block(&format!("while {}", expr(condition)), body)
+            }
+            Stmt::Break => self.line("break"),
+            Stmt::Continue => self.line("continue"),
+            Stmt::Fn {
+                name,
+                params,
+                body,
+                doc,
+                memo,
+            } => {
+                for doc_line in doc.iter().flat_map(|doc| doc.lines()) {
+                    self.line(format!("/// {}", doc_line).trim_end());
+                }
+                match memo {
+                    Some(DEFAULT_MEMO_LIMIT) => self.line("@memo"),
+                    Some(limit) => self.line(&format!("@memo({})", limit)),
+                    None => {}
+                }
+                self.block(&format!("fn {}({})", name, params.join(", ")), body)
+            }
+            Stmt::Call(name, args) => {
+                let text = self.call(name, args, 0);
+                self.line(&text)
+            }
+            Stmt::Expr(value) => {
+                let text = self.call_or_expr(value, 0);
+                self.line(&text)
+            }
+            Stmt::Return(value) => {
+                let value = self.call_or_expr(value, "return ".len());
+                self.line(&format!("return {}", value))
+            }
+            Stmt::Import { module, alias } => match alias {
+                Some(alias) => self.line(&format!("import {} as {}", module, alias)),
+                None => self.line(&format!("import {}", module)),
+            },
+            Stmt::FromImport { module, names } => {
+                self.line(&format!("from {} import {}", module, names.join(", ")))
+            }
+            Stmt::Bench { label, body } => self.block(&format!("bench \"{}\"", label), body),
+            Stmt::Macro { name, args, body } => self.block(&format!("{}({})", name, list(args)), body),
+            Stmt::Cfg {
+                flag,
+                then_branch,
+                else_branch,
+            } => self.conditional(&format!("@if({})", flag), then_branch, else_branch.as_deref()),
+            Stmt::Match { subject, arms } => {
+                self.header(&format!("match {}", expr(subject)));
+                self.depth += 1;
+                for arm in arms {
+                    self.block(&match_arm(arm), &arm.body);
+                }
+                self.depth -= 1;
+                self.line("}");
+            }
+        }
+    }
+
+    /// Writes an `if` or `@if` statement with an optional `else` block.
+    fn conditional(&mut self, header: &str, then_branch: &[Stmt], else_branch: Option<&[Stmt]>) {
+        self.block(header, then_branch);
+        if let Some(else_branch) = else_branch {
+            match self.options.brace_style {
+                BraceStyle::SameLine => {
+                    self.out.pop();
+                    self.out.push_str(" else");
+                    self.open_brace();
+                }
+                BraceStyle::NextLine => {
+                    self.line("else");
+                    self.line("{");
+                }
+            }
+            self.body(else_branch);
+        }
+    }
+
+    /// Writes `header` followed by a braced block containing `body`.
+    fn block(&mut self, header: &str, body: &[Stmt]) {
+        self.header(header);
+        self.body(body);
+    }
+
+    /// Writes a block header and its opening brace.
+    fn header(&mut self, header: &str) {
+        self.line(header);
+        self.out.pop();
+        self.open_brace();
+    }
+
+    /// Writes the opening brace after a header that has just been written
+    /// without its line break.
+    fn open_brace(&mut self) {
+        match self.options.brace_style {
+            BraceStyle::SameLine => self.out.push_str(" {\n"),
+            BraceStyle::NextLine => {
+                self.out.push('\n');
+                self.line("{");
+            }
+        }
+    }
+
+    /// Writes the statements of a block and its closing brace.
+    fn body(&mut self, body: &[Stmt]) {
+        self.depth += 1;
+        self.stmts(body);
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    /// Formats an expression, wrapping it if it is a call that doesn't fit
+    /// after `prefix_len` characters.
+    fn call_or_expr(&self, value: &Expr, prefix_len: usize) -> String {
+        match value {
+            Expr::Call(name, args) => self.call(name, args, prefix_len),
+            _ => expr(value),
+        }
+    }
+
+    /// Formats a call, putting each argument on its own line if the call
+    /// doesn't fit on one line after `prefix_len` characters.
+    fn call(&self, name: &str, args: &[Expr], prefix_len: usize) -> String {
+        let flat = format!("{}({})", name, list(args));
+        let width = self.depth * self.options.indent_width + prefix_len + flat.len();
+        if width <= self.options.max_line_length || args.is_empty() {
+            return flat;
+        }
+        let indent = " ".repeat(self.options.indent_width);
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| format!("{}{}", indent, expr(arg)))
+            .collect();
+        format!("{}(\n{}\n)", name, args.join(",\n"))
+    }
+}
+
+/// Formats the patterns and guard of a match arm, up to its `=>`.
+fn match_arm(arm: &MatchArm) -> String {
+    let patterns: Vec<String> = arm
+        .patterns
+        .iter()
+        .map(|pattern| match pattern {
+            Pattern::Int(i) => i.to_string(),
+            Pattern::Str(s) => format!("\"{}\"", s),
+            Pattern::Bind(name) => name.clone(),
+            Pattern::Wildcard => "_".to_string(),
+        })
+        .collect();
+    match &arm.guard {
+        Some(guard) => format!("{} if {} =>", patterns.join(" | "), expr(guard)),
+        None => format!("{} =>", patterns.join(" | ")),
+    }
+}
+
+/// Formats a comma-separated list of expressions.
+fn list(args: &[Expr]) -> String {
+    args.iter().map(expr).collect::<Vec<_>>().join(", ")
+}
+
+/// How tightly an operator binds; comparisons bind tightest in this grammar.
+fn precedence(op: &Op) -> u8 {
+    match op {
+        Op::Add | Op::Sub => 1,
+        Op::Mul | Op::Div | Op::FloorDiv => 2,
+        _ => 3,
+    }
+}
+
+/// Returns the source text of an operator.
+pub(crate) fn op_str(op: &Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::FloorDiv => "//",
+        Op::Greater => ">",
+        Op::Less => "<",
+        Op::GreaterEq => ">=",
+        Op::LessEq => "<=",
+        Op::Equal => "==",
+        Op::NotEqual => "!=",
+    }
+}
+
+/// Formats an expression on one line.
+fn expr(e: &Expr) -> String {
+    match e {
+        Expr::Int(i) => i.to_string(),
+        Expr::Str(s) => format!("\"{}\"", s),
+        Expr::Var(name) => name.clone(),
+        Expr::Call(name, args) => format!("{}({})", name, list(args)),
+        Expr::Trace { expr: traced, .. } => format!("trace({})", expr(traced)),
+        Expr::Binary(left, op, right) => {
+            let level = precedence(op);
+            // Operators are left-associative, and comparison operands must
+            // be primaries, so only a left operand may share the level.
+            let left_min = if level == 3 { 4 } else { level };
+            format!(
+                "{} {} {}",
+                operand(left, left_min),
+                op_str(op),
+                operand(right, level + 1)
+            )
+        }
+        Expr::Chain(first, rest) => {
+            let mut text = operand(first, 4);
+            for (op, right) in rest {
+                text.push_str(&format!(" {} {}", op_str(op), operand(right, 4)));
+            }
+            text
+        }
+    }
+}
+
+/// Formats an operand, parenthesizing it unless it binds at least as
+/// tightly as `min_precedence`.
+fn operand(e: &Expr, min_precedence: u8) -> String {
+    let level = match e {
+        Expr::Binary(_, op, _) => precedence(op),
+        Expr::Chain(..) => 3,
+        _ => u8::MAX,
+    };
+    if level < min_precedence {
+        format!("({})", expr(e))
+    } else {
+        expr(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source_layout() {
+        let source = "/// Doubles.\nfn  double(x){return x*2}\nlet y=(1+2)*double( 3 )\nif y>=1{print(y)}else{match y{1|2 if y<5=>{break}\n_=>{}}}";
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            formatted,
+            "/// Doubles.\nfn double(x) {\n  return x * 2\n}\nlet y = (1 + 2) * double(3)\nif y >= 1 {\n  print(y)\n} else {\n  match y {\n    1 | 2 if y < 5 => {\n      break\n    }\n    _ => {\n    }\n  }\n}\n"
+        );
+        assert_eq!(format_source(&formatted, &FormatOptions::default()).unwrap(), formatted);
+
+        let options = FormatOptions {
+            indent_width: 4,
+            brace_style: BraceStyle::NextLine,
+            max_line_length: 20,
+        };
+        let formatted = format_source("while x<5{log_info(\"counting\",\"x\",x)}", &options).unwrap();
+        assert_eq!(
+            formatted,
+            "while x < 5\n{\n    log_info(\n        \"counting\",\n        \"x\",\n        x\n    )\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_range_keeps_other_text() {
+        let source = "let a   =  1\n\n\nlet  b=a+1\nprint( b )\n";
+        let span = Span {
+            start: 17,
+            end: 18,
+            ..Span::default()
+        };
+        let formatted = format_range(source, span, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "let a   =  1\n\n\nlet b = a + 1\nprint( b )\n");
+    }
+}
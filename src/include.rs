@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+use crate::ast::{Expr, Stmt};
+
+/// Replaces every `include_str("path")` call in a program with the contents
+/// of the file, read relative to `base_dir`, so the program no longer needs
+/// the file when it runs.
+///
+/// Scripts and modules loaded from a file have their includes resolved this
+/// way before they run. Programs parsed from a string keep the calls, which
+/// then read the file relative to `Context::base_dir` at runtime.
+///
+/// # Arguments
+/// * `stmts` - The parsed program.
+/// * `base_dir` - The directory of the script the program was read from.
+///
+/// # Returns
+/// A message naming the file if one couldn't be read, or if an
+/// `include_str` call has anything other than a single string literal.
+pub fn resolve_includes(stmts: &mut [Stmt], base_dir: &Path) -> Result<(), String> {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Print(expr) | Stmt::Let(_, expr) | Stmt::Expr(expr) | Stmt::Return(expr) => {
+                include_expr(expr, base_dir)?
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                include_expr(condition, base_dir)?;
+                resolve_includes(then_branch, base_dir)?;
+                if let Some(else_branch) = else_branch {
+                    resolve_includes(else_branch, base_dir)?;
+                }
+            }
+            Stmt::While { condition, body } => {
+                include_expr(condition, base_dir)?;
+                resolve_includes(body, base_dir)?;
+            }
+            Stmt::Fn { body, .. } | Stmt::Bench { body, .. } => resolve_includes(body, base_dir)?,
+            Stmt::Call(_, args) => {
+                for arg in args {
+                    include_expr(arg, base_dir)?;
+                }
+            }
+            Stmt::Macro { args, body, .. } => {
+                for arg in args {
+                    include_expr(arg, base_dir)?;
+                }
+                resolve_includes(body, base_dir)?;
+            }
+            Stmt::Match { subject, arms } => {
+                include_expr(subject, base_dir)?;
+                for arm in arms {
+                    if let Some(guard) = &mut arm.guard {
+                        include_expr(guard, base_dir)?;
+                    }
+                    resolve_includes(&mut arm.body, base_dir)?;
+                }
+            }
+            Stmt::Cfg {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                resolve_includes(then_branch, base_dir)?;
+                if let Some(else_branch) = else_branch {
+                    resolve_includes(else_branch, base_dir)?;
+                }
+            }
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } | Stmt::FromImport { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `include_str` calls within an expression.
+fn include_expr(expr: &mut Expr, base_dir: &Path) -> Result<(), String> {
+    match expr {
+        Expr::Call(name, args) if name == "include_str" => {
+            let [Expr::Str(path)] = args.as_slice() else {
+                return Err("include_str() expects a single string literal".to_string());
+            };
+            *expr = Expr::Str(read_include(base_dir, path)?);
+        }
+        Expr::Call(_, args) => {
+            for arg in args {
+                include_expr(arg, base_dir)?;
+            }
+        }
+        Expr::Trace { expr, .. } => include_expr(expr, base_dir)?,
+        Expr::Binary(left, _, right) => {
+            include_expr(left, base_dir)?;
+            include_expr(right, base_dir)?;
+        }
+        Expr::Chain(first, rest) => {
+            include_expr(first, base_dir)?;
+            for (_, operand) in rest {
+                include_expr(operand, base_dir)?;
+            }
+        }
+        Expr::Int(_) | Expr::Str(_) | Expr::Var(_) => {}
+    }
+    Ok(())
+}
+
+/// Reads an included file, normalizing line endings the way scripts are.
+pub(crate) fn read_include(base_dir: &Path, path: &str) -> Result<String, String> {
+    let file = base_dir.join(path);
+    fs::read_to_string(&file)
+        .map(|text| text.replace("\r\n", "\n"))
+        .map_err(|e| format!("Cannot include '{}': {}", file.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_includes_are_embedded() {
+        let dir = std::env::temp_dir().join(format!("dash-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("greeting.txt"), "hello\r\nworld\n").unwrap();
+
+        let mut program = parse("fn greet() {\n  print(include_str(\"greeting.txt\"))\n}\n").unwrap();
+        resolve_includes(&mut program, &dir).unwrap();
+        let Stmt::Fn { body, .. } = &program[0] else {
+            panic!("expected a function");
+        };
+        assert!(matches!(&body[0], Stmt::Print(Expr::Str(text)) if text == "hello\nworld\n"));
+
+        let mut missing = parse("let x = include_str(\"missing.txt\")").unwrap();
+        assert!(resolve_includes(&mut missing, &dir).unwrap_err().contains("missing.txt"));
+        let mut computed = parse("let x = include_str(name)").unwrap();
+        assert!(resolve_includes(&mut computed, &dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ast::Stmt;
+use crate::diagnostic::Span;
+use crate::parser::{parse_with_ranges, ParseError};
+
+/// A parsed file that remembers where each top-level statement came from,
+/// so that later edits can be reparsed incrementally.
+#[derive(Debug, Clone)]
+pub struct ParsedFile {
+    /// The top-level statements, in source order.
+    pub statements: Vec<Stmt>,
+    /// The source location of each statement in `statements`.
+    pub spans: Vec<Span>,
+}
+
+/// A single text replacement, expressed in byte offsets of the old source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    /// Start of the replaced range.
+    pub start: usize,
+    /// End of the replaced range (exclusive).
+    pub old_end: usize,
+    /// Length in bytes of the text that replaced it.
+    pub new_len: usize,
+}
+
+/// Parses a whole file, keeping statement locations for `parse_incremental`.
+///
+/// # Arguments
+/// * `source` - The source code to parse.
+///
+/// # Returns
+/// The parsed file, or the Pest error describing the first syntax error.
+pub fn parse_file(source: &str) -> Result<ParsedFile, ParseError> {
+    let lines = LineIndex::new(source);
+    let mut file = ParsedFile {
+        statements: Vec::new(),
+        spans: Vec::new(),
+    };
+    for ((start, end), stmt) in parse_with_ranges(source)? {
+        file.statements.push(stmt);
+        file.spans.push(lines.span(start, end));
+    }
+    Ok(file)
+}
+
+/// Reparses a file after edits, reusing the top-level statements the edits
+/// didn't touch.
+///
+/// Only the text between the last untouched statement before the edits and
+/// the first untouched statement after them is parsed again. If that region
+/// doesn't parse on its own (for example because an edit opened a block that
+/// now extends further), the whole file is parsed instead.
+///
+/// # Arguments
+/// * `old` - The previous parse of the file.
+/// * `edits` - The edits that turned the old source into `new_source`, in old
+///   source offsets. They must not overlap.
+/// * `new_source` - The full edited source.
+///
+/// # Returns
+/// The updated file and the spans, in `new_source`, of the statements that
+/// were parsed again.
+pub fn parse_incremental(
+    old: &ParsedFile,
+    edits: &[Edit],
+    new_source: &str,
+) -> Result<(ParsedFile, Vec<Span>), ParseError> {
+    let (Some(lo), Some(hi)) = (
+        edits.iter().map(|e| e.start).min(),
+        edits.iter().map(|e| e.old_end).max(),
+    ) else {
+        return Ok((old.clone(), Vec::new()));
+    };
+    let delta: isize = edits
+        .iter()
+        .map(|e| e.new_len as isize - (e.old_end - e.start) as isize)
+        .sum();
+    let shift = |offset: usize| (offset as isize + delta) as usize;
+
+    // Statements entirely before or after the edited range are kept as they
+    // are. A statement that merely touches an edit is reparsed, since the edit
+    // may extend it.
+    let keep_before = old.spans.iter().take_while(|s| s.end < lo).count();
+    let keep_after_from = keep_before
+        + old.spans[keep_before..]
+            .iter()
+            .take_while(|s| s.start <= hi)
+            .count();
+
+    let region_start = if keep_before > 0 { old.spans[keep_before - 1].end } else { 0 };
+    let region_end = match old.spans.get(keep_after_from) {
+        Some(span) => shift(span.start),
+        None => new_source.len(),
+    };
+
+    let lines = LineIndex::new(new_source);
+    let reparsed = match new_source
+        .get(region_start..region_end)
+        .map(parse_with_ranges)
+    {
+        Some(Ok(reparsed)) => reparsed,
+        _ => {
+            let file = parse_file(new_source)?;
+            let changed = file.spans.clone();
+            return Ok((file, changed));
+        }
+    };
+
+    let mut file = ParsedFile {
+        statements: old.statements[..keep_before].to_vec(),
+        spans: old.spans[..keep_before].to_vec(),
+    };
+    let mut changed = Vec::new();
+    for ((start, end), stmt) in reparsed {
+        let span = lines.span(region_start + start, region_start + end);
+        file.statements.push(stmt);
+        file.spans.push(span);
+        changed.push(span);
+    }
+    for (stmt, span) in old.statements[keep_after_from..]
+        .iter()
+        .zip(&old.spans[keep_after_from..])
+    {
+        file.statements.push(stmt.clone());
+        file.spans.push(lines.span(shift(span.start), shift(span.end)));
+    }
+    Ok((file, changed))
+}
+
+/// Byte offsets of line starts, for turning offsets into line/column pairs.
+struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    fn new(source: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { starts }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line = self.starts.partition_point(|&s| s <= start);
+        Span {
+            start,
+            end,
+            line,
+            column: start - self.starts[line - 1] + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "let a = 1\nprint(a)\nlet b = 2\n";
+
+    #[test]
+    fn test_reuses_untouched_statements() {
+        let old = parse_file(SOURCE).unwrap();
+        assert_eq!(old.spans[1].line, 2);
+
+        // Replace `print(a)` with `print(a + 10)`.
+        let new_source = "let a = 1\nprint(a + 10)\nlet b = 2\n";
+        let edit = Edit {
+            start: 17,
+            old_end: 17,
+            new_len: 5,
+        };
+        let (file, changed) = parse_incremental(&old, &[edit], new_source).unwrap();
+
+        assert_eq!(file.statements.len(), 3);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(&new_source[changed[0].start..changed[0].end], "print(a + 10)");
+        assert_eq!(file.spans[2].start, 24);
+        assert_eq!(file.spans[2].line, 3);
+        assert!(matches!(&file.statements[1], Stmt::Print(crate::ast::Expr::Binary(..))));
+    }
+
+    #[test]
+    fn test_inserted_statement_and_fallback() {
+        let old = parse_file(SOURCE).unwrap();
+
+        let inserted = "let a = 1\nprint(a)\nlet z = 0\nlet b = 2\n";
+        let edit = Edit {
+            start: 19,
+            old_end: 19,
+            new_len: 10,
+        };
+        let (file, changed) = parse_incremental(&old, &[edit], inserted).unwrap();
+        assert_eq!(file.statements.len(), 4);
+        // The insertion point is also the start of `let b`, so both are reparsed.
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].line, 3);
+        assert_eq!(changed[1].line, 4);
+
+        // A trailing `+` makes the first statement continue into the next
+        // one, so the edited region alone no longer parses and the whole file
+        // is parsed again.
+        let continued = "let a = 1 +\nprint(a)\nlet b = 2\n";
+        let edit = Edit {
+            start: 9,
+            old_end: 9,
+            new_len: 2,
+        };
+        let (file, changed) = parse_incremental(&old, &[edit], continued).unwrap();
+        assert_eq!(file.statements.len(), 2);
+        assert_eq!(changed, file.spans);
+    }
+}
@@ -0,0 +1,294 @@
+use std::rc::Rc;
+
+use crate::ast::{Context, Expr, Stmt};
+use crate::cfg::apply_defines;
+use crate::eval::{catch_runtime_error, eval_expr, exec_stmt, is_truthy, select_arm};
+use crate::parser::{parse, ParseError};
+
+/// The state of an `Interpreter` after a call to `step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepResult {
+    /// The step limit was reached; call `step` again to continue.
+    Running,
+    /// The program has finished.
+    Done,
+    /// The program stopped with a runtime error.
+    Error(String),
+    /// The program called `wait(value)` and won't continue until
+    /// `Interpreter::resume` is called. The host decides what the value
+    /// means, such as a number of frames or milliseconds.
+    Suspended(String),
+}
+
+/// Runs a program a few statements at a time, keeping its state between
+/// calls, so a host such as a game loop can advance scripts a little each
+/// frame without threads.
+///
+/// ```
+/// use dash_lang::interpreter::{Interpreter, StepResult};
+///
+/// let mut npc = Interpreter::new("let x = 0\nwhile x < 3 {\n  let x = x + 1\n}").unwrap();
+/// assert_eq!(npc.step(2), StepResult::Running);
+/// assert_eq!(npc.context().variables["x"], "0");
+/// while npc.step(2) == StepResult::Running {}
+/// assert_eq!(npc.context().variables["x"], "3");
+/// ```
+pub struct Interpreter {
+    ctx: Context,
+    /// The blocks being executed, innermost last.
+    stack: Vec<Cursor>,
+    /// The argument of the `wait` call the program is suspended at.
+    waiting: Option<String>,
+}
+
+/// A position within a block of statements.
+struct Cursor {
+    stmts: Rc<Vec<Stmt>>,
+    next: usize,
+    /// The condition of the `while` loop this block is the body of.
+    condition: Option<Expr>,
+    /// Whether the loop condition has been tested for this iteration.
+    checked: bool,
+}
+
+impl Cursor {
+    /// Goes back to the start of the loop, testing its condition again.
+    fn restart(&mut self) {
+        self.next = 0;
+        self.checked = false;
+    }
+}
+
+impl Interpreter {
+    /// Parses a program to run in a fresh `Context`.
+    pub fn new(source: &str) -> Result<Self, ParseError> {
+        Self::with_context(source, Context::default())
+    }
+
+    /// Parses a program to run in an existing `Context`, selecting `@if`
+    /// blocks using its defines. The context's prelude is not run.
+    pub fn with_context(source: &str, ctx: Context) -> Result<Self, ParseError> {
+        let stmts = apply_defines(parse(source)?, &ctx.defines);
+        Ok(Interpreter {
+            ctx,
+            stack: vec![Cursor {
+                stmts: Rc::new(stmts),
+                next: 0,
+                condition: None,
+                checked: false,
+            }],
+            waiting: None,
+        })
+    }
+
+    /// Executes at most `max_statements` statements.
+    ///
+    /// Entering an `if`, `while` or `match` body and testing a loop condition
+    /// each count as a statement. A function call runs to completion within
+    /// the statement that makes it.
+    ///
+    /// After an error or once the program is done, further calls return
+    /// `Done`. While the program is suspended by `wait`, they return
+    /// `Suspended` without running anything.
+    pub fn step(&mut self, max_statements: usize) -> StepResult {
+        for _ in 0..max_statements {
+            if let Some(value) = &self.waiting {
+                return StepResult::Suspended(value.clone());
+            }
+            match catch_runtime_error(|| self.advance()) {
+                Ok(true) => {}
+                Ok(false) => return StepResult::Done,
+                Err(message) => {
+                    self.stack.clear();
+                    self.ctx.unwind_to(0);
+                    return StepResult::Error(message);
+                }
+            }
+        }
+        match &self.waiting {
+            Some(value) => StepResult::Suspended(value.clone()),
+            None if self.is_done() => StepResult::Done,
+            None => StepResult::Running,
+        }
+    }
+
+    /// Lets a program suspended by `wait` continue with its next statement.
+    pub fn resume(&mut self) {
+        self.waiting = None;
+    }
+
+    /// Returns `true` once there is nothing left to run.
+    pub fn is_done(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// The context the program runs in.
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// The context the program runs in, for hosts that change variables
+    /// between steps.
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.ctx
+    }
+
+    /// Executes the next statement.
+    ///
+    /// # Returns
+    /// `false` if the program had already finished.
+    fn advance(&mut self) -> bool {
+        loop {
+            let Some(cursor) = self.stack.last_mut() else {
+                return false;
+            };
+            if let Some(condition) = &cursor.condition {
+                if !cursor.checked {
+                    self.ctx.stats.statements += 1;
+                    if eval_expr(condition, &mut self.ctx) == "0" {
+                        self.stack.pop();
+                    } else {
+                        cursor.checked = true;
+                    }
+                    return true;
+                }
+            }
+            if cursor.next < cursor.stmts.len() {
+                let (stmts, index) = (cursor.stmts.clone(), cursor.next);
+                cursor.next += 1;
+                self.exec(&stmts[index]);
+                return true;
+            }
+            if cursor.condition.is_some() {
+                cursor.restart();
+            } else {
+                self.stack.pop();
+            }
+        }
+    }
+
+    /// Executes one statement, entering its body if it has one.
+    fn exec(&mut self, stmt: &Stmt) {
+        let ctx = &mut self.ctx;
+        match stmt {
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                ctx.stats.statements += 1;
+                let branch = if is_truthy(&eval_expr(condition, ctx)) {
+                    Some(then_branch)
+                } else {
+                    else_branch.as_ref()
+                };
+                if let Some(branch) = branch {
+                    self.enter(branch, None);
+                }
+            }
+            Stmt::While { condition, body } => {
+                ctx.stats.statements += 1;
+                self.enter(body, Some(condition.clone()));
+            }
+            Stmt::Match { subject, arms } => {
+                ctx.stats.statements += 1;
+                if let Some(arm) = select_arm(subject, arms, ctx) {
+                    self.enter(&arm.body, None);
+                }
+            }
+            Stmt::Break | Stmt::Continue => {
+                ctx.stats.statements += 1;
+                // Leave the blocks inside the innermost loop, then the loop
+                // itself for `break` or its current iteration for `continue`.
+                while let Some(cursor) = self.stack.last_mut() {
+                    if cursor.condition.is_some() {
+                        if matches!(stmt, Stmt::Break) {
+                            self.stack.pop();
+                        } else {
+                            cursor.restart();
+                        }
+                        return;
+                    }
+                    if self.stack.len() == 1 {
+                        return;
+                    }
+                    self.stack.pop();
+                }
+            }
+            Stmt::Call(name, args)
+                if name == "wait"
+                    && !ctx.functions.contains_key(name)
+                    && !ctx.disabled_builtins.contains(name) =>
+            {
+                ctx.stats.statements += 1;
+                if args.len() != 1 {
+                    panic!("Function 'wait' expected 1 args, got {}", args.len());
+                }
+                self.waiting = Some(eval_expr(&args[0], ctx));
+            }
+            _ => {
+                exec_stmt(stmt, ctx);
+            }
+        }
+    }
+
+    /// Starts executing a block, as the body of a loop if `condition` is set.
+    fn enter(&mut self, stmts: &[Stmt], condition: Option<Expr>) {
+        self.stack.push(Cursor {
+            stmts: Rc::new(stmts.to_vec()),
+            next: 0,
+            condition,
+            checked: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_preserves_state() {
+        let source = "let x = 0\nwhile x < 10 {\n  let x = x + 1\n  if x == 2 {\n    continue\n  }\n  if x == 4 {\n    break\n  }\n}\nlet done = 1\n";
+        let mut interpreter = Interpreter::new(source).unwrap();
+        let mut steps = 0;
+        while interpreter.step(1) == StepResult::Running {
+            steps += 1;
+        }
+        assert_eq!(interpreter.context().variables["x"], "4");
+        assert_eq!(interpreter.context().variables["done"], "1");
+        assert!(steps > 10);
+        assert_eq!(interpreter.step(5), StepResult::Done);
+
+        let mut failing = Interpreter::new("let a = 1\nlet b = missing\nlet c = 3\n").unwrap();
+        assert_eq!(failing.step(1), StepResult::Running);
+        assert_eq!(failing.step(5), StepResult::Error("Undefined variable: missing".to_string()));
+        assert!(!failing.context().variables.contains_key("c"));
+        assert_eq!(failing.step(1), StepResult::Done);
+    }
+
+    #[test]
+    fn test_wait_suspends_until_resumed() {
+        let source = "let n = 0
+while n < 2 {
+  let n = n + 1
+  wait(n * 10)
+}
+let after = 1
+";
+        let mut interpreter = Interpreter::new(source).unwrap();
+        assert_eq!(interpreter.step(100), StepResult::Suspended("10".to_string()));
+        assert_eq!(interpreter.step(100), StepResult::Suspended("10".to_string()));
+        interpreter.resume();
+        assert_eq!(interpreter.step(100), StepResult::Suspended("20".to_string()));
+        interpreter.resume();
+        assert_eq!(interpreter.step(100), StepResult::Done);
+        assert_eq!(interpreter.context().variables["after"], "1");
+
+        let mut nested = Interpreter::new("fn pause() {\n  wait(1)\n}\npause()\n").unwrap();
+        let StepResult::Error(message) = nested.step(10) else {
+            panic!("expected an error");
+        };
+        assert!(message.contains("outside function bodies"));
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ast::Context;
+use crate::eval::check_interrupt;
+
+/// The runtime error message raised when a script is interrupted.
+pub const INTERRUPTED: &str = "Interrupted";
+
+/// Set by the Ctrl-C handler installed by `install_handler`.
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C interrupt the running script at its next statement instead
+/// of killing the process, so that `on_interrupt` and `on_exit` hooks get to
+/// run. Only the CLI should call this; embedders interrupt scripts through
+/// `Context::interrupt`.
+pub fn install_handler() {
+    #[cfg(unix)]
+    {
+        const SIGINT: i32 = 2;
+        extern "C" {
+            fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        }
+        extern "C" fn on_sigint(_: i32) {
+            SIGNALLED.store(true, Ordering::SeqCst);
+        }
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+        unsafe {
+            signal(SIGINT, on_sigint);
+        }
+    }
+}
+
+/// How often `sleep_until` and the input builtins wake to check for an
+/// interrupt.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Waits until `until`, waking regularly so that an interrupt or a passed
+/// deadline stops the script with an `INTERRUPTED` error, as it would
+/// between statements, instead of after the wait.
+pub(crate) fn sleep_until(until: Instant, ctx: &mut Context) {
+    loop {
+        check_interrupt(ctx);
+        let now = Instant::now();
+        if now >= until {
+            return;
+        }
+        thread::sleep((until - now).min(POLL_INTERVAL));
+    }
+}
+
+/// Returns `true` if Ctrl-C has been pressed since the last `take_signal`,
+/// leaving it to be taken.
+pub(crate) fn signal_pending() -> bool {
+    SIGNALLED.load(Ordering::SeqCst)
+}
+
+/// Returns `true` once for each Ctrl-C received since the last call.
+pub(crate) fn take_signal() -> bool {
+    SIGNALLED.swap(false, Ordering::SeqCst)
+}
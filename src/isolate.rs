@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::ast::{ContextBuilder, LoopControl, Stmt};
+use crate::cfg::apply_defines;
+use crate::eval::{catch_runtime_error, exec_program, exec_stmt, run_exit_hooks};
+use crate::interrupt;
+use crate::output::Capture;
+use crate::parser::parse;
+use crate::program::Program;
+use crate::prompt::Input;
+use crate::temp::remove_temp_paths;
+
+/// The call depth jobs are limited to when `Limits::max_depth` is `None`.
+pub const DEFAULT_MAX_DEPTH: usize = 1_000;
+
+/// The string length jobs are limited to when `Limits::max_string_len` is
+/// `None`.
+pub const DEFAULT_MAX_STRING_LEN: usize = 16 * 1024 * 1024;
+
+/// Stack reserved on a worker for each level of function calls, with room
+/// for the statements and expressions a call nests inside.
+const STACK_PER_CALL: usize = 64 * 1024;
+
+/// Stack a worker needs besides that for function calls.
+const BASE_STACK: usize = 2 * 1024 * 1024;
+
+/// Resources a single job may use before it is stopped with an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Wall-clock time the job may run for.
+    pub timeout: Option<Duration>,
+    /// Statements the job may execute, including those of its functions.
+    pub max_statements: Option<u64>,
+    /// How deeply the job's function calls may nest. `None` means
+    /// `DEFAULT_MAX_DEPTH` rather than no limit, since runaway recursion
+    /// would otherwise overflow the worker's stack and abort the host.
+    pub max_depth: Option<usize>,
+    /// How many bytes a string built by `repeat`, `pad_left` or `pad_right`
+    /// may hold. `None` means `DEFAULT_MAX_STRING_LEN`, so that a job can't
+    /// exhaust the host's memory in one call.
+    pub max_string_len: Option<usize>,
+}
+
+impl Limits {
+    /// The call depth jobs are held to.
+    fn depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// The string length jobs are held to.
+    fn string_len(&self) -> usize {
+        self.max_string_len.unwrap_or(DEFAULT_MAX_STRING_LEN)
+    }
+}
+
+/// A script for an `IsolatePool` to run.
+pub enum Script {
+    /// Source code, parsed by the worker that runs it.
+    Source(String),
+    /// A program compiled ahead of time, which can be shared between jobs.
+    Program(Arc<Program>),
+}
+
+impl From<&str> for Script {
+    fn from(source: &str) -> Self {
+        Script::Source(source.to_string())
+    }
+}
+
+impl From<String> for Script {
+    fn from(source: String) -> Self {
+        Script::Source(source)
+    }
+}
+
+impl From<Program> for Script {
+    fn from(program: Program) -> Self {
+        Script::Program(Arc::new(program))
+    }
+}
+
+impl From<Arc<Program>> for Script {
+    fn from(program: Arc<Program>) -> Self {
+        Script::Program(program)
+    }
+}
+
+/// What a job produced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobOutput {
+    /// The value of the script's top-level `return`, or an empty string if
+    /// it finished without one.
+    pub value: String,
+    /// Everything the script printed.
+    pub output: String,
+}
+
+/// A job waiting for a worker, with the channel its result is sent back on.
+struct Job {
+    script: Script,
+    inputs: HashMap<String, String>,
+    reply: Sender<Result<JobOutput, String>>,
+}
+
+/// A fixed set of worker threads that run scripts in isolation from each
+/// other, for hosts that run many small, possibly untrusted, scripts.
+///
+/// Every job gets a fresh `Context` built from the pool's `ContextBuilder`,
+/// so nothing a script defines is visible to later jobs. Each worker parses
+/// the builder's prelude once when it starts rather than for every job.
+/// Jobs have no input: `read_line` finds none and the prompt builtins fail,
+/// rather than reading the host's stdin.
+///
+/// ```
+/// use dash_lang::isolate::{IsolatePool, Limits};
+/// use dash_lang::ContextBuilder;
+/// use std::collections::HashMap;
+///
+/// let pool = IsolatePool::new(2, ContextBuilder::new(), Limits::default());
+/// let inputs = HashMap::from([("price".to_string(), "20".to_string())]);
+/// let job = pool.submit("print(price)\nreturn price * 2", inputs).unwrap();
+/// assert_eq!(job.value, "40");
+/// assert_eq!(job.output, "20\n");
+/// ```
+pub struct IsolatePool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl IsolatePool {
+    /// Starts `size` workers, at least one. Each worker's stack is sized for
+    /// the call depth in `limits`.
+    ///
+    /// # Arguments
+    /// * `size` - The number of jobs that can run at the same time.
+    /// * `builder` - The configuration of each job's context.
+    /// * `limits` - The resources each job may use.
+    pub fn new(size: usize, builder: ContextBuilder, limits: Limits) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let stack_size = BASE_STACK.saturating_add(limits.depth().saturating_mul(STACK_PER_CALL));
+        let workers = (0..size.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                let builder = builder.clone();
+                thread::Builder::new()
+                    .stack_size(stack_size)
+                    .spawn(move || work(&queue, &builder, limits))
+                    .expect("Cannot start an isolate worker")
+            })
+            .collect();
+        IsolatePool {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    /// Runs a script on the next free worker and waits for it to finish.
+    ///
+    /// # Arguments
+    /// * `script` - Source code or a compiled `Program`.
+    /// * `inputs` - Variables set before the script starts.
+    ///
+    /// # Returns
+    /// What the job produced, or a message if the script couldn't be
+    /// parsed, failed at runtime or exceeded a limit.
+    pub fn submit(
+        &self,
+        script: impl Into<Script>,
+        inputs: HashMap<String, String>,
+    ) -> Result<JobOutput, String> {
+        let (reply, result) = mpsc::channel();
+        let job = Job {
+            script: script.into(),
+            inputs,
+            reply,
+        };
+        self.jobs.as_ref().unwrap().send(job).map_err(|_| "The pool has stopped".to_string())?;
+        result.recv().map_err(|_| "The worker running the job stopped".to_string())?
+    }
+}
+
+impl Drop for IsolatePool {
+    /// Lets the workers finish their current jobs, then stops them.
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Runs jobs from `queue` until the pool is dropped.
+fn work(queue: &Mutex<Receiver<Job>>, builder: &ContextBuilder, limits: Limits) {
+    let prelude = builder.clone().build().prelude.map(|source| parse(&source));
+    loop {
+        // The lock is released as soon as a job has been taken.
+        let Ok(job) = queue.lock().unwrap().recv() else {
+            return;
+        };
+        let result = match &prelude {
+            Some(Err(e)) => Err(format!("Parse error in prelude: {}", e)),
+            Some(Ok(prelude)) => run_job(&job, prelude, builder, limits),
+            None => run_job(&job, &[], builder, limits),
+        };
+        let _ = job.reply.send(result);
+    }
+}
+
+/// Runs one job in a fresh context.
+///
+/// The job's `on_exit` hooks run only if it succeeds, since a job that failed
+/// or timed out mustn't go on running, but its temporary files are deleted
+/// however it ends.
+fn run_job(job: &Job, prelude: &[Stmt], builder: &ContextBuilder, limits: Limits) -> Result<JobOutput, String> {
+    let parsed;
+    let program = match &job.script {
+        Script::Source(source) => {
+            parsed = parse(source).map_err(|e| format!("Parse error: {}", e))?;
+            &parsed
+        }
+        Script::Program(program) => &program.entry,
+    };
+
+    let mut ctx = builder.clone().build();
+    let capture = Capture::default();
+    ctx.output.sink = Box::new(capture.clone());
+    ctx.input = Input {
+        source: Box::new(io::empty()),
+        prompts: Box::new(io::sink()),
+        terminal: false,
+    };
+    if let Script::Program(program) = &job.script {
+        if let Some(dir) = program.path.parent() {
+            ctx.base_dir = dir.to_path_buf();
+        }
+        ctx.parsed_modules
+            .extend(program.modules.iter().map(|(path, stmts)| (path.clone(), stmts.clone())));
+    }
+    for (name, value) in &job.inputs {
+        ctx.set_variable(name, value.clone());
+    }
+    ctx.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+    ctx.statement_limit = limits.max_statements;
+    ctx.depth_limit = Some(limits.depth());
+    ctx.string_limit = Some(limits.string_len());
+    let prelude = apply_defines(prelude.to_vec(), &ctx.defines);
+    let program = apply_defines(program.clone(), &ctx.defines);
+
+    let result = catch_runtime_error(|| {
+        exec_program(&prelude, &mut ctx);
+        let mut value = String::new();
+        for stmt in &program {
+            if let LoopControl::Return(returned) = exec_stmt(stmt, &mut ctx) {
+                value = returned;
+                break;
+            }
+        }
+        run_exit_hooks(&mut ctx);
+        value
+    });
+    remove_temp_paths(&mut ctx);
+    match result {
+        Ok(value) => Ok(JobOutput {
+            value,
+            output: capture.contents(),
+        }),
+        Err(message) if message == interrupt::INTERRUPTED && limits.timeout.is_some() => {
+            Err(format!("Timed out after {:?}", limits.timeout.unwrap()))
+        }
+        Err(message) => Err(format!("Runtime error: {}", message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Permissions;
+    use crate::eval::INTERRUPT_GRACE;
+
+    #[test]
+    fn test_jobs_are_isolated_and_limited() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(200)),
+            max_statements: Some(10_000),
+            max_depth: None,
+            max_string_len: None,
+        };
+        let pool = IsolatePool::new(2, ContextBuilder::new(), limits);
+        pool.submit("let leaked = 1", HashMap::new()).unwrap();
+        assert!(pool.submit("return leaked", HashMap::new()).is_err());
+
+        let err = pool.submit("while 1 {\n  let x = 1\n}", HashMap::new()).unwrap_err();
+        assert_eq!(err, "Runtime error: Statement limit of 10000 exceeded");
+
+        let pool = IsolatePool::new(1, ContextBuilder::new(), Limits {
+            timeout: Some(Duration::from_millis(50)),
+            max_statements: None,
+            max_depth: None,
+            max_string_len: None,
+        });
+        let err = pool.submit("while 1 {\n  let x = 1\n}", HashMap::new()).unwrap_err();
+        assert!(err.starts_with("Timed out after"), "{}", err);
+
+        let script = "fn stall() {\n  while 1 {\n    let x = 1\n  }\n}\non_interrupt(\"stall\")\nstall()";
+        let started = Instant::now();
+        let err = pool.submit(script, HashMap::new()).unwrap_err();
+        assert!(err.starts_with("Timed out after"), "{}", err);
+        assert!(started.elapsed() < Duration::from_millis(50) + INTERRUPT_GRACE * 2);
+
+        let err = pool.submit("return repeat(\"x\", INT_MAX)", HashMap::new()).unwrap_err();
+        assert_eq!(err, format!("Runtime error: String length limit of {} exceeded", DEFAULT_MAX_STRING_LEN));
+        let err = pool.submit("return pad_left(\"x\", 20000000, \"-\")", HashMap::new()).unwrap_err();
+        assert!(err.contains("String length limit"), "{}", err);
+    }
+
+    #[test]
+    fn test_recursion_inputs_and_cleanup() {
+        let limits = Limits {
+            max_statements: Some(100_000),
+            ..Limits::default()
+        };
+        let builder = ContextBuilder::new().permissions(Permissions {
+            allow_fs: true,
+            ..Permissions::default()
+        });
+        let pool = IsolatePool::new(1, builder, limits);
+        let err = pool.submit("fn f(n) {\n  return f(n + 1)\n}\nf(0)", HashMap::new()).unwrap_err();
+        assert_eq!(err, format!("Runtime error: Call depth limit of {} exceeded", DEFAULT_MAX_DEPTH));
+
+        let inputs = HashMap::from([("price".to_string(), "20".to_string())]);
+        let job = pool.submit("let price = 5\nreturn price", inputs).unwrap();
+        assert_eq!(job.value, "5");
+        let job = pool.submit("let line = read_line()\nreturn has_line()", HashMap::new()).unwrap();
+        assert_eq!(job.value, "0");
+
+        let suffix = format!(".isolate-{}", std::process::id());
+        let script = format!("let path = temp_file(\"{}\")\nreturn missing", suffix);
+        assert!(pool.submit(script, HashMap::new()).is_err());
+        let leaked = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .any(|entry| entry.unwrap().file_name().to_string_lossy().ends_with(&suffix));
+        assert!(!leaked);
+    }
+}
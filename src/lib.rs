@@ -1,7 +1,63 @@
 pub mod ast;
+pub mod bench;
+pub mod builtins;
+pub mod cfg;
+pub mod collate;
+pub mod complete;
+pub mod diagnostic;
+pub mod doc;
+pub mod dump;
+mod encoding;
 pub mod eval;
+pub mod events;
+pub mod expr_program;
+pub mod format;
+pub mod include;
+pub mod incremental;
+pub mod interpreter;
+pub mod interrupt;
+pub mod isolate;
+pub mod lint;
+pub mod logging;
+pub mod macros;
+pub mod memo;
+pub mod meta;
+mod json;
+pub mod module;
+pub mod notebook;
+pub mod output;
+pub mod package;
 pub mod parser;
+pub mod paths;
+pub mod program;
+pub mod prompt;
+pub mod random;
+pub mod refactor;
+pub mod repl;
+pub mod snapshot;
+pub mod shared;
+pub mod stats;
+pub mod store;
+pub mod symbols;
+pub mod temp;
+pub mod terminal;
+pub mod timing;
+pub mod units;
 
-pub use parser::{DashParser, run};
-pub use ast::{Expr, Stmt, Context};
-pub use eval::{eval_expr, exec_stmt};
+pub use parser::{DashParser, ParseError, parse, parse_expr, parse_with_diagnostics, run, run_with_context};
+pub use ast::{Expr, Stmt, Context, ContextBuilder, ErrorMode, Permissions};
+pub use eval::{catch_runtime_error, eval_expr, exec_program, exec_stmt, run_exit_hooks};
+pub use cfg::apply_defines;
+pub use complete::{complete, Completion, CompletionKind};
+pub use diagnostic::{apply_fixes, Diagnostic, Fix, Severity, Span};
+pub use lint::{check_calls, check_strict, lint};
+pub use logging::{LogFormat, LogLevel, Logger};
+pub use events::{run_due_events, run_events, Timers};
+pub use expr_program::ExprProgram;
+pub use format::{format_range, format_source, BraceStyle, FormatOptions};
+pub use include::resolve_includes;
+pub use incremental::{parse_file, parse_incremental, Edit, ParsedFile};
+pub use program::Program;
+pub use repl::repl;
+pub use stats::Stats;
+pub use symbols::{analyze, Symbol, SymbolKind, SymbolTable, Unresolved};
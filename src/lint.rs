@@ -0,0 +1,373 @@
+use pest::iterators::Pair;
+use pest::Parser;
+
+use std::collections::HashMap;
+
+use crate::ast::{MatchArm, Pattern, Stmt};
+use crate::builtins::BUILTINS;
+use crate::diagnostic::{suggest, Diagnostic, Severity, Span};
+use crate::meta::ScriptMeta;
+use crate::parser::{DashParser, Rule};
+use crate::symbols::analyze;
+
+/// Runs the static checks over a program and returns any problems found.
+///
+/// Currently this inspects every `match` statement for arms that can never be
+/// taken and for missing catch-all arms.
+///
+/// # Arguments
+/// * `program` - The statements of the program to check.
+///
+/// # Returns
+/// The diagnostics, in source order.
+pub fn lint(program: &[Stmt]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    lint_block(program, &mut diagnostics);
+    diagnostics
+}
+
+/// Reports calls to functions that aren't defined in the source, imported
+/// by name with `from`, or builtin, suggesting a similarly named function
+/// where there is one.
+///
+/// Calls through a namespace (`utils.parse`) aren't checked, and nor is
+/// anything if the source doesn't parse. Hosts that add functions through a
+/// prelude should expect warnings for them.
+///
+/// # Arguments
+/// * `source` - The program's source code.
+///
+/// # Returns
+/// A warning per unknown call, in source order, with a fix if a suggestion was found.
+pub fn check_calls(source: &str) -> Vec<Diagnostic> {
+    let Ok(pairs) = DashParser::parse(Rule::program, source) else {
+        return Vec::new();
+    };
+    let mut known: Vec<&str> = BUILTINS.to_vec();
+    known.push("print");
+    let mut calls = Vec::new();
+    for pair in pairs.flatten() {
+        match pair.as_rule() {
+            Rule::fn_stmt => {
+                let name = pair.into_inner().find(|p| p.as_rule() == Rule::ident);
+                known.extend(name.map(|p| p.as_str()));
+            }
+            Rule::from_stmt => known.extend(pair.into_inner().skip(1).map(|p| p.as_str())),
+            Rule::qualified_name if !pair.as_str().contains('.') => calls.push(pair),
+            _ => {}
+        }
+    }
+
+    calls
+        .into_iter()
+        .filter(|call| !known.contains(&call.as_str()))
+        .map(|call| unknown_call(call, &known))
+        .collect()
+}
+
+/// Reports, as errors, code that is certain to fail when it runs:
+///
+/// - calls to unknown functions, as found by `check_calls`;
+/// - calls to a function defined in the source with the wrong number of
+///   arguments, unless it is defined more than once with different arities;
+/// - variables read in a function body (or at top level) that never assigns
+///   them anywhere. At top level, the arguments declared in the script's
+///   metadata header count as assigned.
+///
+/// These are the checks enabled by `--check --strict`.
+///
+/// # Arguments
+/// * `source` - The program's source code.
+///
+/// # Returns
+/// The errors, in source order, or nothing if the source doesn't parse.
+pub fn check_strict(source: &str) -> Vec<Diagnostic> {
+    let (Ok(pairs), Ok(table)) = (DashParser::parse(Rule::program, source), analyze(source)) else {
+        return Vec::new();
+    };
+    let mut errors: Vec<Diagnostic> = check_calls(source)
+        .into_iter()
+        .map(|d| Diagnostic {
+            severity: Severity::Error,
+            ..d
+        })
+        .collect();
+
+    let mut arities: HashMap<&str, Option<usize>> = HashMap::new();
+    let mut calls = Vec::new();
+    for pair in pairs.flatten() {
+        match pair.as_rule() {
+            Rule::fn_stmt => {
+                let mut parts = pair.into_inner().skip_while(|p| p.as_rule() != Rule::ident);
+                let name = parts.next().unwrap().as_str();
+                let arity = parts
+                    .next()
+                    .filter(|p| p.as_rule() == Rule::param_list)
+                    .map_or(0, |params| params.into_inner().count());
+                let entry = arities.entry(name).or_insert(Some(arity));
+                if *entry != Some(arity) {
+                    *entry = None;
+                }
+            }
+            Rule::call_expr => {
+                let mut parts = pair.into_inner();
+                let name = parts.next().unwrap();
+                let args = parts.next().map_or(0, |args| args.into_inner().count());
+                calls.push((name, args));
+            }
+            _ => {}
+        }
+    }
+    for (name, args) in calls {
+        if let Some(Some(params)) = arities.get(name.as_str()) {
+            if args != *params {
+                errors.push(Diagnostic::error(
+                    format!(
+                        "function `{}` takes {} args but is called with {}",
+                        name.as_str(),
+                        params,
+                        args
+                    ),
+                    name.as_span().into(),
+                ));
+            }
+        }
+    }
+
+    let header_args = ScriptMeta::parse(source)
+        .ok()
+        .flatten()
+        .map(|meta| meta.arg_variables())
+        .unwrap_or_default();
+    for variable in &table.unresolved_variables {
+        let assigned = (variable.scope.is_none() && header_args.contains(&variable.name))
+            || table
+                .symbols
+                .iter()
+                .any(|s| s.name == variable.name && s.scope == variable.scope);
+        if !assigned {
+            let place = match &variable.scope {
+                Some(function) => format!("in function `{}`", function),
+                None => "at top level".to_string(),
+            };
+            errors.push(Diagnostic::error(
+                format!("variable `{}` is never assigned {}", variable.name, place),
+                variable.span,
+            ));
+        }
+    }
+
+    errors.sort_by_key(|d| d.span.start);
+    errors
+}
+
+/// Builds the diagnostic for a call to an unknown function.
+fn unknown_call(call: Pair<Rule>, known: &[&str]) -> Diagnostic {
+    let name = call.as_str();
+    let span = Span::from(call.as_span());
+    match suggest(name, known.iter().copied()) {
+        Some(suggestion) => Diagnostic::warning(
+            format!("unknown function `{}`; did you mean `{}`?", name, suggestion),
+            span,
+        )
+        .with_fix(span, suggestion),
+        None => Diagnostic::warning(format!("unknown function `{}`", name), span),
+    }
+}
+
+/// Recursively checks a block of statements, including nested blocks.
+fn lint_block(stmts: &[Stmt], diagnostics: &mut Vec<Diagnostic>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Stmt::Cfg {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                lint_block(then_branch, diagnostics);
+                if let Some(else_branch) = else_branch {
+                    lint_block(else_branch, diagnostics);
+                }
+            }
+            Stmt::While { body, .. }
+            | Stmt::Fn { body, .. }
+            | Stmt::Bench { body, .. }
+            | Stmt::Macro { body, .. } => lint_block(body, diagnostics),
+            Stmt::Match { arms, .. } => {
+                lint_match(arms, diagnostics);
+                for arm in arms {
+                    lint_block(&arm.body, diagnostics);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the arms of a single `match` statement.
+///
+/// Reports arms that follow an unguarded catch-all, literal patterns already
+/// handled by an earlier unguarded arm, and matches with no catch-all at all.
+/// Since values are integers or strings, only a `_` or binding arm without a
+/// guard makes a match exhaustive.
+fn lint_match(arms: &[MatchArm], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: Vec<&Pattern> = Vec::new();
+    let mut catch_all = false;
+
+    for arm in arms {
+        if catch_all {
+            diagnostics.push(Diagnostic::warning(
+                "unreachable match arm: an earlier arm matches every value",
+                arm.span,
+            ));
+            continue;
+        }
+
+        let duplicates: Vec<&Pattern> = arm
+            .patterns
+            .iter()
+            .filter(|p| seen.iter().any(|s| same_literal(s, p)))
+            .collect();
+        if !duplicates.is_empty() && duplicates.len() == arm.patterns.len() {
+            diagnostics.push(Diagnostic::warning(
+                "unreachable match arm: every pattern is matched by an earlier arm",
+                arm.span,
+            ));
+        } else {
+            for pattern in duplicates {
+                diagnostics.push(Diagnostic::warning(
+                    format!(
+                        "duplicate pattern {} is already matched by an earlier arm",
+                        describe(pattern)
+                    ),
+                    arm.span,
+                ));
+            }
+        }
+
+        if arm.guard.is_none() {
+            seen.extend(arm.patterns.iter());
+            catch_all = arm
+                .patterns
+                .iter()
+                .any(|p| matches!(p, Pattern::Wildcard | Pattern::Bind(_)));
+        }
+    }
+
+    if !catch_all {
+        if let Some(last) = arms.last() {
+            diagnostics.push(Diagnostic::warning(
+                "non-exhaustive match: add a `_` arm to handle remaining values",
+                last.span,
+            ));
+        }
+    }
+}
+
+/// Returns `true` if both patterns are the same literal.
+fn same_literal(a: &Pattern, b: &Pattern) -> bool {
+    match (a, b) {
+        (Pattern::Int(x), Pattern::Int(y)) => x == y,
+        (Pattern::Str(x), Pattern::Str(y)) => x == y,
+        _ => false,
+    }
+}
+
+/// Renders a literal pattern for use in a diagnostic message.
+fn describe(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Int(i) => format!("`{}`", i),
+        Pattern::Str(s) => format!("`\"{}\"`", s),
+        Pattern::Bind(name) => format!("`{}`", name),
+        Pattern::Wildcard => "`_`".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_duplicate_and_unreachable_arms() {
+        let program = parse(
+            r#"
+match n {
+  1 | 2 => {
+    print("a")
+  }
+  2 | 3 => {
+    print("b")
+  }
+  _ => {
+    print("c")
+  }
+  4 => {
+    print("d")
+  }
+}
+"#,
+        )
+        .unwrap();
+        let diagnostics = lint(&program);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].message.contains("duplicate pattern `2`"));
+        assert_eq!(diagnostics[0].span.line, 6);
+        assert!(diagnostics[1].message.contains("unreachable"));
+        assert_eq!(diagnostics[1].span.line, 12);
+    }
+
+    #[test]
+    fn test_unknown_calls() {
+        let source = "fn greet(name) {\n  prnt(name)\n}\nfrom utils import parse\ngret(\"x\")\nlet n = parse(len(\"x\"))\nutils.helper()\n";
+        let diagnostics = check_calls(source);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown function `prnt`; did you mean `print`?",
+                "unknown function `gret`; did you mean `greet`?",
+                "unknown function `len`",
+            ]
+        );
+        let fix = diagnostics[1].fix.as_ref().unwrap();
+        assert_eq!((fix.span.line, fix.replacement.as_str()), (5, "greet"));
+    }
+
+    #[test]
+    fn test_strict_errors() {
+        let source = "fn add(a, b) {\n  return a + b + c\n}\nlet x = add(1)\nlet y = w\nwhile x < 3 {\n  print(later)\n  let later = x\n}\nprnt(x)\n";
+        let errors = check_strict(source);
+        let messages: Vec<(usize, &str)> = errors
+            .iter()
+            .map(|d| (d.span.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "variable `c` is never assigned in function `add`"),
+                (4, "function `add` takes 2 args but is called with 1"),
+                (5, "variable `w` is never assigned at top level"),
+                (10, "unknown function `prnt`; did you mean `print`?"),
+            ]
+        );
+        assert!(errors.iter().all(|d| d.severity == Severity::Error));
+
+        let header = "--- args: [env, --dry-run] ---\nprint(env)\nprint(dry_run)\nfn f() {\n  return env\n}\n";
+        let messages: Vec<String> = check_strict(header).into_iter().map(|d| d.message).collect();
+        assert_eq!(messages, ["variable `env` is never assigned in function `f`"]);
+    }
+
+    #[test]
+    fn test_missing_catch_all() {
+        let program = parse("match n {\n  1 => {\n    print(1)\n  }\n}").unwrap();
+        let diagnostics = lint(&program);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("non-exhaustive"));
+    }
+}
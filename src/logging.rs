@@ -0,0 +1,164 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::json;
+
+/// Severity of a log record. Levels are ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => Err(format!("Unknown log level: {}", s)),
+        }
+    }
+}
+
+/// How log records are written to the sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `[INFO] message key=value`
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Destination and filtering settings for the `log_*` builtins.
+pub struct Logger {
+    /// Records below this level are discarded.
+    pub min_level: LogLevel,
+    /// The output format for records.
+    pub format: LogFormat,
+    /// Where records are written. Defaults to stderr.
+    pub sink: Box<dyn Write>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger {
+            min_level: LogLevel::Info,
+            format: LogFormat::Text,
+            sink: Box::new(io::stderr()),
+        }
+    }
+}
+
+impl Logger {
+    /// Writes a single record, unless its level is below `min_level`.
+    ///
+    /// # Arguments
+    /// * `level` - The severity of the record.
+    /// * `message` - The log message.
+    /// * `fields` - Additional structured key/value data.
+    pub fn log(&mut self, level: LogLevel, message: &str, fields: &[(&str, &str)]) {
+        if level < self.min_level {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Text => {
+                let mut line = format!("[{}] {}", level.to_string().to_uppercase(), message);
+                for (key, value) in fields {
+                    if value.is_empty() || value.contains(char::is_whitespace) {
+                        line.push_str(&format!(" {}={}", key, json::quote(value)));
+                    } else {
+                        line.push_str(&format!(" {}={}", key, value));
+                    }
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut line = format!(
+                    "{{\"level\":{},\"msg\":{}",
+                    json::quote(&level.to_string()),
+                    json::quote(message)
+                );
+                for (key, value) in fields {
+                    line.push_str(&format!(",{}:{}", json::quote(key), json::quote(value)));
+                }
+                line.push('}');
+                line
+            }
+        };
+        let _ = writeln!(self.sink, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A sink that keeps everything written to it.
+    #[derive(Clone, Default)]
+    struct Capture(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_level_filter_and_formats() {
+        let capture = Capture::default();
+        let mut logger = Logger {
+            min_level: LogLevel::Info,
+            format: LogFormat::Text,
+            sink: Box::new(capture.clone()),
+        };
+        logger.log(LogLevel::Debug, "hidden", &[]);
+        logger.log(LogLevel::Warn, "disk low", &[("free", "3 GB"), ("mount", "/")]);
+        logger.format = LogFormat::Json;
+        logger.log(LogLevel::Error, "failed", &[("code", "2")]);
+
+        let output = String::from_utf8(capture.0.borrow().clone()).unwrap();
+        assert_eq!(
+            output,
+            "[WARN] disk low free=\"3 GB\" mount=/\n\
+             {\"level\":\"error\",\"msg\":\"failed\",\"code\":\"2\"}\n"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::ast::{Expr, MatchArm, Stmt};
+use crate::parser::{parse, ParseError};
+
+/// The statement in a macro's template that stands for the block the macro
+/// is used with.
+pub const BODY_PLACEHOLDER: &str = "body";
+
+/// A statement template registered by the host, which lets scripts use
+/// syntax the grammar doesn't have.
+///
+/// A use of a macro is written `name(args) { ... }`. It runs the template
+/// with every read of a parameter replaced by the matching argument
+/// expression, and every `body()` statement replaced by the block. Arguments
+/// are substituted rather than evaluated first, so one read more than once
+/// is evaluated each time. Variables the template assigns are set in the
+/// scope the macro is used in.
+///
+/// ```
+/// use dash_lang::macros::Macro;
+/// use dash_lang::{run_with_context, Context};
+///
+/// let mut ctx = Context::default();
+/// let times = Macro::new(&["n"], "let times_left = n\nwhile times_left > 0 {\n  body()\n  let times_left = times_left - 1\n}").unwrap();
+/// ctx.define_macro("times", times);
+/// run_with_context("let x = 1\ntimes(3) {\n  let x = x * 2\n}", &mut ctx);
+/// assert_eq!(ctx.variables["x"], "8");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Macro {
+    params: Vec<String>,
+    template: Vec<Stmt>,
+}
+
+impl Macro {
+    /// Parses a template.
+    ///
+    /// # Arguments
+    /// * `params` - The names the template uses for the macro's arguments.
+    /// * `template` - Dash source using the parameters, with `body()` where
+    ///   the block goes.
+    ///
+    /// # Returns
+    /// The macro, or the error if the template doesn't parse.
+    pub fn new(params: &[&str], template: &str) -> Result<Self, ParseError> {
+        Ok(Macro {
+            params: params.iter().map(|param| param.to_string()).collect(),
+            template: parse(template)?,
+        })
+    }
+
+    /// Returns the statements a use of the macro stands for.
+    ///
+    /// # Arguments
+    /// * `name` - The macro's name, for the error if the arguments don't fit.
+    /// * `args` - The argument expressions of the use.
+    /// * `body` - The block of the use.
+    pub fn expand(&self, name: &str, args: &[Expr], body: &[Stmt]) -> Vec<Stmt> {
+        if args.len() != self.params.len() {
+            panic!(
+                "Macro '{}' expected {} args, got {}",
+                name,
+                self.params.len(),
+                args.len()
+            );
+        }
+        let expansion = Expansion {
+            params: &self.params,
+            args,
+            body,
+        };
+        expansion.stmts(&self.template)
+    }
+}
+
+/// The substitutions for one use of a macro.
+struct Expansion<'a> {
+    params: &'a [String],
+    args: &'a [Expr],
+    body: &'a [Stmt],
+}
+
+impl Expansion<'_> {
+    fn stmts(&self, stmts: &[Stmt]) -> Vec<Stmt> {
+        let mut result = Vec::with_capacity(stmts.len());
+        for stmt in stmts {
+            match stmt {
+                Stmt::Call(name, args) if name == BODY_PLACEHOLDER && args.is_empty() => {
+                    result.extend(self.body.iter().cloned())
+                }
+                _ => result.push(self.stmt(stmt)),
+            }
+        }
+        result
+    }
+
+    fn stmt(&self, stmt: &Stmt) -> Stmt {
+        match stmt {
+            Stmt::Print(expr) => Stmt::Print(self.expr(expr)),
+            Stmt::Let(name, expr) => Stmt::Let(name.clone(), self.expr(expr)),
+            Stmt::Expr(expr) => Stmt::Expr(self.expr(expr)),
+            Stmt::Return(expr) => Stmt::Return(self.expr(expr)),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => Stmt::If {
+                condition: self.expr(condition),
+                then_branch: self.stmts(then_branch),
+                else_branch: else_branch.as_ref().map(|branch| self.stmts(branch)),
+            },
+            Stmt::While { condition, body } => Stmt::While {
+                condition: self.expr(condition),
+                body: self.stmts(body),
+            },
+            Stmt::Call(name, args) => Stmt::Call(name.clone(), self.exprs(args)),
+            Stmt::Match { subject, arms } => Stmt::Match {
+                subject: self.expr(subject),
+                arms: arms
+                    .iter()
+                    .map(|arm| MatchArm {
+                        guard: arm.guard.as_ref().map(|guard| self.expr(guard)),
+                        body: self.stmts(&arm.body),
+                        ..arm.clone()
+                    })
+                    .collect(),
+            },
+            Stmt::Bench { label, body } => Stmt::Bench {
+                label: label.clone(),
+                body: self.stmts(body),
+            },
+            Stmt::Cfg {
+                flag,
+                then_branch,
+                else_branch,
+            } => Stmt::Cfg {
+                flag: flag.clone(),
+                then_branch: self.stmts(then_branch),
+                else_branch: else_branch.as_ref().map(|branch| self.stmts(branch)),
+            },
+            Stmt::Macro { name, args, body } => Stmt::Macro {
+                name: name.clone(),
+                args: self.exprs(args),
+                body: self.stmts(body),
+            },
+            // A function defined by the template has its own scope, where
+            // the macro's parameters aren't visible.
+            Stmt::Fn { .. }
+            | Stmt::Break
+            | Stmt::Continue
+            | Stmt::Import { .. }
+            | Stmt::FromImport { .. } => stmt.clone(),
+        }
+    }
+
+    fn exprs(&self, exprs: &[Expr]) -> Vec<Expr> {
+        exprs.iter().map(|expr| self.expr(expr)).collect()
+    }
+
+    fn expr(&self, expr: &Expr) -> Expr {
+        match expr {
+            Expr::Var(name) => match self.params.iter().position(|param| param == name) {
+                Some(index) => self.args[index].clone(),
+                None => expr.clone(),
+            },
+            Expr::Call(name, args) => Expr::Call(name.clone(), self.exprs(args)),
+            Expr::Trace {
+                expr: traced,
+                source,
+                line,
+                column,
+            } => Expr::Trace {
+                expr: Box::new(self.expr(traced)),
+                source: source.clone(),
+                line: *line,
+                column: *column,
+            },
+            Expr::Binary(left, op, right) => {
+                Expr::Binary(Box::new(self.expr(left)), op.clone(), Box::new(self.expr(right)))
+            }
+            Expr::Chain(first, rest) => Expr::Chain(
+                Box::new(self.expr(first)),
+                rest.iter().map(|(op, operand)| (op.clone(), self.expr(operand))).collect(),
+            ),
+            Expr::Int(_) | Expr::Str(_) => expr.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Context;
+    use crate::eval::catch_runtime_error;
+    use crate::parser::run_with_context;
+
+    #[test]
+    fn test_macros_substitute_args_and_body() {
+        let mut ctx = Context::default();
+        let unless = Macro::new(&["cond"], "if cond == 0 {\n  body()\n}").unwrap();
+        ctx.define_macro("unless", unless);
+        run_with_context(
+            "let hits = 0\nlet limit = 2\nunless(limit > 5) {\n  let hits = hits + 1\n}\nunless(limit < 5) {\n  let hits = 100\n}",
+            &mut ctx,
+        );
+        assert_eq!(ctx.variables["hits"], "1");
+
+        let err = catch_runtime_error(|| run_with_context("unless(1, 2) {\n}", &mut ctx)).unwrap_err();
+        assert_eq!(err, "Macro 'unless' expected 1 args, got 2");
+        let err = catch_runtime_error(|| run_with_context("retry(3) {\n}", &mut ctx)).unwrap_err();
+        assert_eq!(err, "Undefined macro: retry");
+    }
+}
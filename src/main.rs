@@ -1,29 +1,138 @@
-use dash_lang::run;
+use dash_lang::bench::{self, Benchmarks};
+use dash_lang::meta::ScriptMeta;
+use dash_lang::notebook;
+use dash_lang::snapshot::{self, capture_output, snapshot_path};
+use dash_lang::timing::LineTimes;
+use dash_lang::{apply_defines, apply_fixes, catch_runtime_error, check_calls, check_strict, doc, eval_expr, Diagnostic, Severity, Span, interrupt, package, exec_program, lint, parse, parse_with_diagnostics, repl, resolve_includes, run, run_exit_hooks, Context, ErrorMode, Expr, LogFormat, LogLevel};
 use pest_derive::Parser;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 /// Pest parser definition using the grammar in `dash.pest`.
 #[derive(Parser)]
 #[grammar = "dash.pest"]
 pub struct DashParser;
 
+/// Command-line options accepted by the interpreter.
+#[derive(Default)]
+struct Options {
+    /// Parse and lint the script instead of running it (`--check`).
+    check: bool,
+    /// With `--check`, also report code certain to fail at runtime as errors (`--strict`).
+    strict: bool,
+    /// Open a REPL in the failing scope when a runtime error occurs (`--debug-on-error`).
+    debug_on_error: bool,
+    /// Print execution statistics to stderr when the script finishes (`--stats`).
+    stats: bool,
+    /// Print the statements that took the most time when the script finishes (`--time-lines`).
+    time_lines: bool,
+    /// Let the script call `eval` (`--allow-eval`).
+    allow_eval: bool,
+    /// Minimum level for the `log_*` builtins (`--log-level`).
+    log_level: Option<LogLevel>,
+    /// Output format for the `log_*` builtins (`--log-format`).
+    log_format: Option<LogFormat>,
+    /// Whether a failing top-level statement stops the script (`--on-error`).
+    error_mode: ErrorMode,
+    /// How errors and diagnostics are written to stderr (`--error-format`).
+    error_format: ErrorFormat,
+    /// File to write a snapshot of the script's state to when it stops (`--dump-state`).
+    dump_state: Option<PathBuf>,
+    /// Wall-clock limit for running the script (`--timeout`).
+    timeout: Option<Timeout>,
+    /// Flags that select `@if(flag)` blocks (`--define`, repeatable).
+    defines: HashSet<String>,
+    /// Extra module search directories (`--path`, repeatable).
+    module_paths: Vec<PathBuf>,
+    /// Package dependencies by name, resolved by `dash run`.
+    packages: HashMap<String, PathBuf>,
+    /// Time `bench` blocks with these settings (`dash bench`).
+    benchmarks: Option<Benchmarks>,
+    /// The script to run.
+    filename: Option<String>,
+    /// Arguments following the script, parsed against its header's `args`.
+    script_args: Vec<String>,
+    /// Function to call once the script has run (`--call`).
+    call: Option<String>,
+    /// Arguments for the `--call` function (`--arg`, repeatable).
+    call_args: Vec<String>,
+}
+
 /// Entry point for the CLI interpreter.
 /// If a filename is provided, it runs the script from that file.
 /// Otherwise, it runs a default hardcoded script.
+///
+/// With `--check`, the script is parsed and linted instead of being run.
+/// With `--debug-on-error`, a runtime error opens a REPL in the failing scope.
+/// `dash doc <file>` renders documentation instead of running anything,
+/// `dash fix <file>` applies suggested fixes, `dash bench <file>` times the
+/// script's `bench` blocks, `dash test` compares scripts' output against
+/// snapshots, `dash notebook <file.md>` runs a document's code blocks, `dash repl`
+/// starts an interactive session, and `dash run` / `dash add`
+/// work with the package described by `dash.toml`.
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("doc") => {
+            args.next();
+            return doc_command(args);
+        }
+        Some("add") => {
+            args.next();
+            return add_command(args);
+        }
+        Some("fix") => {
+            args.next();
+            return fix_command(args);
+        }
+        _ => {}
+    }
+
+    // Runtime errors are reported by `run_script`, not by the default panic message.
+    panic::set_hook(Box::new(|_| {}));
+
+    if args.peek().map(String::as_str) == Some("repl") {
+        args.next();
+        return repl_command(args);
+    }
+
+    if args.peek().map(String::as_str) == Some("notebook") {
+        args.next();
+        return notebook_command(args);
+    }
 
-    if args.len() > 1 {
+    if args.peek().map(String::as_str) == Some("test") {
+        args.next();
+        return test_command(args);
+    }
+
+    if args.peek().map(String::as_str) == Some("bench") {
+        args.next();
+        return bench_command(args);
+    }
+
+    if args.peek().map(String::as_str) == Some("run") {
+        args.next();
+        return run_command(parse_options(args));
+    }
+
+    let options = parse_options(args);
+    if let Some(filename) = &options.filename {
         // Run from file
-        let filename = &args[1];
-        match fs::read_to_string(filename) {
-            Ok(mut source) => {
-                // Convert CRLF (\r\n) to LF (\n)
-                source = source.replace("\r\n", "\n");
-                run(&source)
-            },
-            Err(e) => eprintln!("Error reading file '{}': {}", filename, e),
+        let source = read_source(filename);
+        if options.check {
+            check_source(&source, &options)
+        } else {
+            run_script(filename, &source, &options);
         }
     } else {
         // Run hardcoded script (fallback)
@@ -37,3 +146,751 @@ while x < 5 {
     }
 }
 
+/// Parses the options shared by plain `dash <file>` and `dash run`.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--strict" => options.strict = true,
+            "--debug-on-error" => options.debug_on_error = true,
+            "--stats" => options.stats = true,
+            "--time-lines" => options.time_lines = true,
+            "--allow-eval" => options.allow_eval = true,
+            "--log-level" => options.log_level = Some(parse_value(&arg, args.next())),
+            "--log-format" => options.log_format = Some(parse_value(&arg, args.next())),
+            "--on-error" => options.error_mode = parse_value(&arg, args.next()),
+            "--error-format" => options.error_format = parse_value(&arg, args.next()),
+            "--dump-state" => options.dump_state = Some(flag_value(&arg, args.next()).into()),
+            "--timeout" => options.timeout = Some(parse_value(&arg, args.next())),
+            "--define" => {
+                options.defines.insert(flag_value(&arg, args.next()));
+            }
+            "--path" => options.module_paths.push(flag_value(&arg, args.next()).into()),
+            _ => {
+                // Everything after the script belongs to the script, except
+                // for choosing a function to call.
+                options.filename = Some(arg);
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--call" => options.call = Some(flag_value(&arg, args.next())),
+                        "--arg" => options.call_args.push(flag_value(&arg, args.next())),
+                        _ => options.script_args.push(arg),
+                    }
+                }
+                break;
+            }
+        }
+    }
+    options
+}
+
+/// Implements `dash run [options] [file]`, which runs a script of the package
+/// whose `dash.toml` is in the current directory or one of its parents.
+/// Without a file, the manifest's entry script is run. Dependencies are
+/// fetched if needed and made importable by name.
+fn run_command(mut options: Options) {
+    let (manifest, root) = load_manifest();
+    options.packages = package::resolve_dependencies(&manifest, &root).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    let filename = options
+        .filename
+        .clone()
+        .unwrap_or_else(|| root.join(&manifest.entry).to_string_lossy().into_owned());
+    let source = read_source(&filename);
+    if options.check {
+        check_source(&source, &options)
+    } else {
+        run_script(&filename, &source, &options);
+    }
+}
+
+/// Implements `dash add <name> (--path <dir> | --git <url>)`, which adds a
+/// dependency to the nearest `dash.toml` or replaces an existing one.
+fn add_command(mut args: impl Iterator<Item = String>) {
+    let mut name = None;
+    let mut source = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--path" => source = Some(package::Source::Path(flag_value(&arg, args.next()))),
+            "--git" => source = Some(package::Source::Git(flag_value(&arg, args.next()))),
+            _ => name = Some(arg),
+        }
+    }
+    let (Some(name), Some(source)) = (name, source) else {
+        eprintln!("Usage: dash add <name> (--path <dir> | --git <url>)");
+        process::exit(2);
+    };
+    if !package::is_valid_name(&name) {
+        eprintln!("Invalid dependency name '{}': use only letters, digits and _", name);
+        process::exit(2);
+    }
+
+    let (_, root) = load_manifest();
+    let path = root.join(package::MANIFEST_FILE);
+    let text = read_source(&path.to_string_lossy());
+    let updated = package::add_dependency(&text, &package::Dependency { name, source });
+    if let Err(e) = fs::write(&path, updated) {
+        eprintln!("Error writing file '{}': {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+/// Finds and parses the nearest `dash.toml`, exiting with status 1 if there
+/// is none or it is invalid.
+///
+/// # Returns
+/// The manifest and the directory containing it.
+fn load_manifest() -> (package::Manifest, PathBuf) {
+    let cwd = env::current_dir().unwrap_or_default();
+    let Some(path) = package::find_manifest(&cwd) else {
+        eprintln!("Could not find {} in {} or any parent directory", package::MANIFEST_FILE, cwd.display());
+        process::exit(1);
+    };
+    let manifest = package::Manifest::load(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    (manifest, root)
+}
+
+/// A `--timeout` value such as `30s`, `500ms` or `2m`. A bare number is in seconds.
+struct Timeout(Duration, String);
+
+impl FromStr for Timeout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let invalid = || format!("Invalid timeout '{}' (expected e.g. 30s, 500ms or 2m)", s);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let duration = match unit {
+            "ms" => Duration::from_millis(number),
+            "" | "s" => Duration::from_secs(number),
+            "m" => Duration::from_secs(number * 60),
+            _ => return Err(invalid()),
+        };
+        Ok(Timeout(duration, s.to_string()))
+    }
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.1)
+    }
+}
+
+/// An `--error-format`: readable text, or one JSON object per line.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("Unknown error format '{}' (expected human or json)", s)),
+        }
+    }
+}
+
+/// Writes an error without a source location to stderr: `human` as it is,
+/// or, with `--error-format json`, `message` as a diagnostic with `code`.
+fn report_error(format: ErrorFormat, code: &str, human: &str, message: &str) {
+    match format {
+        ErrorFormat::Human => eprintln!("{}", human),
+        ErrorFormat::Json => eprintln!("{}", Diagnostic::error(message, Span::default()).to_json(code)),
+    }
+}
+
+/// Reports a statement skipped by `--on-error continue` as a JSON diagnostic.
+fn report_runtime_json(message: &str) {
+    report_error(ErrorFormat::Json, "runtime", "", message);
+}
+
+/// Parses and runs a script, exiting with status 1 on a parse or runtime error.
+///
+/// With `--debug-on-error`, a runtime error opens a REPL in the scope that
+/// was active when the error occurred before the process exits. With
+/// `--stats`, execution counters are printed once the script stops, and with
+/// `--time-lines`, the statements that took the most time.
+/// Ctrl-C stops the script at its next statement and exits with status 130.
+/// With `--timeout`, a script still running when the time is up is stopped
+/// the same way and the process exits with status 124. `on_exit` hooks run
+/// however the script stops. With `--on-error continue`, failing top-level
+/// statements are reported and skipped, and the process exits with status 1
+/// at the end if there were any. With `--error-format json`, errors are
+/// written as JSON diagnostics. With `--call`, the named function is called
+/// with the `--arg` values once the script has run, and its result printed;
+/// an integer result from 1 to 255 is also the exit status.
+///
+/// # Returns
+/// The script's context, once it has finished without errors.
+fn run_script(filename: &str, source: &str, options: &Options) -> Context {
+    let program = match parse(source) {
+        Ok(program) => program,
+        Err(e) => {
+            match options.error_format {
+                ErrorFormat::Human => eprintln!("Parse error: {}", e),
+                ErrorFormat::Json => {
+                    for diagnostic in parse_with_diagnostics(source).1 {
+                        eprintln!("{}", diagnostic.to_json("syntax"));
+                    }
+                }
+            }
+            process::exit(1);
+        }
+    };
+
+    let mut ctx = Context::default();
+    if let Some(dir) = Path::new(filename).parent() {
+        ctx.base_dir = dir.to_path_buf();
+    }
+    ctx.defines = options.defines.clone();
+    ctx.permissions.allow_eval = options.allow_eval;
+    ctx.permissions.allow_fs = true;
+    ctx.benchmarks = options.benchmarks.clone();
+    ctx.error_mode = options.error_mode;
+    if options.error_format == ErrorFormat::Json {
+        ctx.report_error = Some(report_runtime_json);
+    }
+    apply_header(filename, source, options, &mut ctx);
+    let mut program = apply_defines(program, &ctx.defines);
+    if let Err(e) = resolve_includes(&mut program, &ctx.base_dir) {
+        report_error(options.error_format, "include", &format!("Error: {}", e), &e);
+        process::exit(1);
+    }
+    ctx.packages = options.packages.clone();
+    for path in &options.module_paths {
+        ctx.add_module_path(path);
+    }
+    if let Some(dash_path) = env::var_os("DASH_PATH") {
+        for path in env::split_paths(&dash_path) {
+            ctx.add_module_path(path);
+        }
+    }
+    if let Some(level) = options.log_level {
+        ctx.logger.min_level = level;
+    }
+    if let Some(format) = options.log_format {
+        ctx.logger.format = format;
+    }
+    if options.time_lines {
+        ctx.line_times = Some(LineTimes::default());
+    }
+    interrupt::install_handler();
+    let timed_out = Arc::new(AtomicBool::new(false));
+    if let Some(timeout) = &options.timeout {
+        let duration = timeout.0;
+        let timed_out = timed_out.clone();
+        let interrupt = ctx.interrupt.clone();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            timed_out.store(true, Ordering::SeqCst);
+            interrupt.store(true, Ordering::SeqCst);
+        });
+    }
+    let result = catch_runtime_error(|| {
+        exec_program(&program, &mut ctx);
+        options.call.as_ref().map(|name| call_entry_point(name, &options.call_args, &mut ctx))
+    });
+    if options.stats {
+        eprintln!("{}", ctx.stats());
+    }
+    if let Some(times) = &ctx.line_times {
+        eprintln!("{}", times);
+    }
+    if let Err(message) = result {
+        if message == interrupt::INTERRUPTED {
+            let timeout = options.timeout.as_ref().filter(|_| timed_out.load(Ordering::SeqCst));
+            match timeout {
+                Some(timeout) => {
+                    let human = format!("Timed out after {}", timeout);
+                    report_error(options.error_format, "timeout", &human, &human);
+                }
+                None => report_error(options.error_format, "interrupted", &message, &message),
+            }
+            finish(&mut ctx, options);
+            process::exit(if timeout.is_some() { 124 } else { 130 });
+        }
+        report_error(options.error_format, "runtime", &format!("Runtime error: {}", message), &message);
+        if options.debug_on_error {
+            match ctx.frames.last() {
+                Some(frame) => eprintln!("Debugging inside function '{}'.", frame.function),
+                None => eprintln!("Debugging at top level."),
+            }
+            eprintln!("Inspect variables or evaluate expressions; press Ctrl-D to exit.");
+            repl(&mut ctx);
+        }
+        finish(&mut ctx, options);
+        process::exit(1);
+    }
+    finish(&mut ctx, options);
+    if !ctx.errors.is_empty() {
+        process::exit(1);
+    }
+    if let Ok(Some(value)) = result {
+        if !value.is_empty() {
+            println!("{}", value);
+        }
+        if let Ok(status @ 1..=255) = value.parse::<i32>() {
+            process::exit(status);
+        }
+    }
+    ctx
+}
+
+/// Calls a function the script defines, for `--call`, passing each
+/// argument as a string.
+///
+/// # Returns
+/// The function's result.
+fn call_entry_point(name: &str, args: &[String], ctx: &mut Context) -> String {
+    match ctx.functions.get(name) {
+        Some((params, _)) if params.len() != args.len() => panic!(
+            "Function '{}' expected {} args, got {}",
+            name,
+            params.len(),
+            args.len()
+        ),
+        Some(_) => {}
+        None => panic!("The script defines no function '{}' to call", name),
+    }
+    let args = args.iter().map(|arg| Expr::Str(arg.clone())).collect();
+    eval_expr(&Expr::Call(name.to_string(), args), ctx)
+}
+
+/// Applies the script's metadata header: checks that the capabilities it
+/// `requires` are granted and sets a variable for each option in its `args`
+/// from the arguments after the script. With `--help` among them, prints
+/// the script's usage and exits. Exits with status 1 if a requirement isn't
+/// granted, or status 2 if the arguments don't match.
+fn apply_header(filename: &str, source: &str, options: &Options, ctx: &mut Context) {
+    let meta = ScriptMeta::parse(source).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    let Some(meta) = meta else {
+        if let Some(arg) = options.script_args.first() {
+            eprintln!("Unexpected argument '{}': the script declares no args in a metadata header", arg);
+            process::exit(2);
+        }
+        return;
+    };
+    if options.script_args.iter().any(|arg| arg == "--help") {
+        print!("{}", meta.usage(&format!("dash {}", filename)));
+        process::exit(0);
+    }
+    let missing = meta.missing_permissions(&ctx.permissions);
+    for required in &missing {
+        match *required {
+            "eval" => eprintln!("Error: the script requires eval; run it with --allow-eval"),
+            _ => eprintln!("Error: the script requires '{}', which dash can't grant", required),
+        }
+    }
+    if !missing.is_empty() {
+        process::exit(1);
+    }
+    match meta.parse_args(&options.script_args) {
+        Ok(values) => {
+            for (name, value) in values {
+                ctx.set_variable(&name, value);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            eprint!("{}", meta.usage(&format!("dash {}", filename)));
+            process::exit(2);
+        }
+    }
+}
+
+/// Implements `dash bench [--warmup <n>] [--iterations <n>] [--baseline <file>]
+/// [--save-baseline <file>] [options] <file>`, which runs a script and times
+/// each of its `bench` blocks, printing mean, median and standard deviation.
+/// With `--baseline`, mean times are compared against a file written earlier
+/// by `--save-baseline`. Other options are those of plain `dash <file>`.
+fn bench_command(mut args: impl Iterator<Item = String>) {
+    let mut benchmarks = Benchmarks::new(3, 20);
+    let mut baseline = None;
+    let mut save_baseline = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--warmup" => benchmarks.warmup = parse_count(&arg, args.next()),
+            "--iterations" => benchmarks.iterations = parse_count(&arg, args.next()),
+            "--baseline" => baseline = Some(flag_value(&arg, args.next())),
+            "--save-baseline" => save_baseline = Some(flag_value(&arg, args.next())),
+            _ => rest.push(arg),
+        }
+    }
+    let mut options = parse_options(rest.into_iter());
+    let Some(filename) = options.filename.clone() else {
+        eprintln!("Usage: dash bench [--warmup <n>] [--iterations <n>] [--baseline <file>] [--save-baseline <file>] <file>");
+        process::exit(2);
+    };
+    let baseline = baseline.map_or_else(HashMap::new, |path| {
+        let text = read_source(&path);
+        bench::parse_baseline(&text).unwrap_or_else(|e| {
+            eprintln!("{} ({})", e, path);
+            process::exit(1);
+        })
+    });
+    options.benchmarks = Some(benchmarks);
+
+    let ctx = run_script(&filename, &read_source(&filename), &options);
+    let benchmarks = ctx.benchmarks.unwrap();
+    if benchmarks.results.is_empty() {
+        eprintln!("No bench blocks were run");
+    }
+    for result in &benchmarks.results {
+        println!("{}", result.summary(baseline.get(&result.label).copied()));
+    }
+    if let Some(path) = save_baseline {
+        if let Err(e) = fs::write(&path, benchmarks.to_json()) {
+            eprintln!("Error writing file '{}': {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Implements `dash repl [--preload <file>...] [--no-rc]`, which starts an
+/// interactive session. The project's `.dashrc`, found in the directory of
+/// the nearest `dash.toml` or else the current directory, is loaded first
+/// unless `--no-rc` is given, then each preloaded file in order. A file that
+/// fails to load is reported and the session starts anyway.
+fn repl_command(args: impl Iterator<Item = String>) {
+    let mut preload = Vec::new();
+    let mut load_rc = true;
+    let mut preloading = false;
+    for arg in args {
+        match arg.as_str() {
+            "--preload" => preloading = true,
+            "--no-rc" => load_rc = false,
+            _ if preloading && !arg.starts_with("--") => preload.push(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Usage: dash repl [--preload <file>...] [--no-rc]");
+                process::exit(2);
+            }
+        }
+    }
+
+    let cwd = env::current_dir().unwrap_or_default();
+    let project = package::find_manifest(&cwd)
+        .and_then(|manifest| manifest.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| cwd.clone());
+    let rc = project.join(dash_lang::repl::RC_FILE);
+    if load_rc && rc.is_file() {
+        preload.insert(0, rc);
+    }
+
+    let mut ctx = Context::default();
+    ctx.base_dir = cwd;
+    ctx.permissions.allow_fs = true;
+    for path in &preload {
+        if let Err(e) = dash_lang::repl::load_file(path, &mut ctx) {
+            eprintln!("{}", e);
+        }
+    }
+    repl(&mut ctx);
+    let _ = catch_runtime_error(|| run_exit_hooks(&mut ctx));
+}
+
+/// Implements `dash notebook [--check] <file.md>`, which runs the document's
+/// `dash` code blocks in one context and rewrites the file with each block's
+/// output below it. With `--check`, the file is left alone and the process
+/// exits with status 1 if any output is out of date.
+fn notebook_command(args: impl Iterator<Item = String>) {
+    let mut check = false;
+    let mut filename = None;
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => filename = Some(arg),
+        }
+    }
+    let Some(filename) = filename else {
+        eprintln!("Usage: dash notebook [--check] <file.md>");
+        process::exit(2);
+    };
+    let markdown = read_source(&filename);
+
+    let mut ctx = Context::default();
+    if let Some(dir) = Path::new(&filename).parent() {
+        ctx.base_dir = dir.to_path_buf();
+    }
+    ctx.permissions.allow_fs = true;
+    interrupt::install_handler();
+    let result = catch_runtime_error(|| notebook::run_notebook(&markdown, &mut ctx));
+    let _ = catch_runtime_error(|| run_exit_hooks(&mut ctx));
+    let updated = result.unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        process::exit(130);
+    });
+
+    if check {
+        if let Some(diff) = snapshot::diff(&markdown, &updated) {
+            println!("{} has out-of-date output (- current, + expected):", filename);
+            print!("{}", diff);
+            process::exit(1);
+        }
+    } else if updated != markdown {
+        if let Err(e) = fs::write(&filename, updated) {
+            eprintln!("Error writing file '{}': {}", filename, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Implements `dash test [--update-snapshots] <file or directory>...`, which
+/// runs each script and compares what it prints with its snapshot, the
+/// `.out` file beside it, showing a line diff for each mismatch. Directories
+/// are searched for `.dash` files, including in subdirectories. With
+/// `--update-snapshots`, the snapshots are written from the current output
+/// instead. Exits with status 1 if any script doesn't match.
+fn test_command(args: impl Iterator<Item = String>) {
+    let mut update = false;
+    let mut scripts = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--update-snapshots" => update = true,
+            _ if Path::new(&arg).is_dir() => {
+                let pattern = Path::new(&arg).join("**").join("*.dash");
+                scripts.extend(dash_lang::paths::glob(&pattern.to_string_lossy()));
+            }
+            _ => scripts.push(PathBuf::from(arg)),
+        }
+    }
+    if scripts.is_empty() {
+        eprintln!("Usage: dash test [--update-snapshots] <file or directory>...");
+        process::exit(2);
+    }
+
+    let mut failed = 0;
+    for script in &scripts {
+        let snapshot = snapshot_path(script);
+        let output = match capture_output(script) {
+            Ok(output) => output,
+            Err(e) => {
+                println!("FAIL {}: {}", script.display(), e);
+                failed += 1;
+                continue;
+            }
+        };
+        if update {
+            if let Err(e) = fs::write(&snapshot, output) {
+                eprintln!("Error writing file '{}': {}", snapshot.display(), e);
+                process::exit(1);
+            }
+            println!("updated {}", snapshot.display());
+            continue;
+        }
+        let Ok(expected) = fs::read_to_string(&snapshot) else {
+            println!("FAIL {}: no snapshot at {} (run with --update-snapshots)", script.display(), snapshot.display());
+            failed += 1;
+            continue;
+        };
+        match snapshot::diff(&expected, &output) {
+            None => println!("ok   {}", script.display()),
+            Some(diff) => {
+                println!("FAIL {}: output differs from {} (- expected, + actual)", script.display(), snapshot.display());
+                print!("{}", diff);
+                failed += 1;
+            }
+        }
+    }
+    if !update {
+        println!("{} passed, {} failed", scripts.len() - failed, failed);
+    }
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+/// Parses the count following a command-line flag, exiting with status 2 if
+/// it is missing or not a number.
+fn parse_count(flag: &str, value: Option<String>) -> usize {
+    flag_value(flag, value).parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for {}: expected a number", flag);
+        process::exit(2);
+    })
+}
+
+/// Runs the script's `on_exit` hooks in the top-level scope, reporting an
+/// error from a hook as a runtime error.
+///
+/// With `--dump-state`, the state is written out first, so that after a
+/// runtime error it still shows the failing function's variables.
+fn finish(ctx: &mut Context, options: &Options) {
+    if let Some(path) = &options.dump_state {
+        if let Err(e) = fs::write(path, ctx.dump().to_json()) {
+            eprintln!("Error writing file '{}': {}", path.display(), e);
+        }
+    }
+    ctx.unwind_to(0);
+    if let Err(message) = catch_runtime_error(|| run_exit_hooks(ctx)) {
+        report_error(options.error_format, "runtime", &format!("Runtime error: {}", message), &message);
+        process::exit(1);
+    }
+}
+
+/// Implements `dash doc <file> [--html] [-o <output>]`, which renders
+/// documentation for a script's functions from their `///` comments.
+/// Markdown is written to stdout unless `--html` or `-o` say otherwise.
+fn doc_command(mut args: impl Iterator<Item = String>) {
+    let mut html = false;
+    let mut output = None;
+    let mut filename = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--html" => html = true,
+            "-o" | "--output" => output = Some(flag_value(&arg, args.next())),
+            _ => filename = Some(arg),
+        }
+    }
+    let Some(filename) = filename else {
+        eprintln!("Usage: dash doc <file> [--html] [-o <output>]");
+        process::exit(2);
+    };
+
+    let program = match parse(&read_source(&filename)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            process::exit(1);
+        }
+    };
+    let title = Path::new(&filename)
+        .file_name()
+        .map_or(filename.clone(), |name| name.to_string_lossy().into_owned());
+    let rendered = if html {
+        doc::render_html(&title, &program)
+    } else {
+        doc::render_markdown(&title, &program)
+    };
+
+    match output {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, rendered) {
+                eprintln!("Error writing file '{}': {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", rendered),
+    }
+}
+
+/// Reads a script from disk, exiting with status 1 if it can't be read.
+fn read_source(filename: &str) -> String {
+    match fs::read_to_string(filename) {
+        // Convert CRLF (\r\n) to LF (\n)
+        Ok(source) => source.replace("\r\n", "\n"),
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", filename, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Returns the value following a command-line flag, exiting with status 2 if
+/// it is missing.
+fn flag_value(flag: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("Missing value for {}", flag);
+        process::exit(2);
+    })
+}
+
+/// Parses the value following a command-line flag, exiting with status 2 if
+/// it is missing or invalid.
+fn parse_value<T: FromStr<Err = String>>(flag: &str, value: Option<String>) -> T {
+    flag_value(flag, value).parse().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    })
+}
+
+/// Parses and lints a script without running it, printing diagnostics to stderr.
+/// Every syntax error is reported, not just the first. Exits with status 1 if
+/// the script fails to parse, or with `--strict` if it is certain to fail at
+/// runtime. With `--error-format json`, errors have the code `check` and
+/// warnings the code `lint`.
+fn check_source(source: &str, options: &Options) {
+    let (errors, warnings) = diagnose(source, options.strict);
+    for diagnostic in errors.iter().chain(&warnings) {
+        match options.error_format {
+            ErrorFormat::Human => eprintln!("{}", diagnostic),
+            ErrorFormat::Json => {
+                let code = if diagnostic.severity == Severity::Error { "check" } else { "lint" };
+                eprintln!("{}", diagnostic.to_json(code));
+            }
+        }
+    }
+    if !errors.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Runs every static check over a script.
+///
+/// # Returns
+/// The errors, and the warnings for the parts that did parse. Calls are only
+/// checked once the whole script parses; with `strict`, unknown calls are
+/// errors, along with the other problems `check_strict` finds.
+fn diagnose(source: &str, strict: bool) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+    let (ast, mut errors) = parse_with_diagnostics(source);
+    let mut warnings = lint(&ast);
+    if errors.is_empty() {
+        if strict {
+            errors.extend(check_strict(source));
+        } else {
+            warnings.extend(check_calls(source));
+        }
+    }
+    (errors, warnings)
+}
+
+/// Implements `dash fix <file>`, which applies the fixes suggested by
+/// `--check` to the file in place and prints the diagnostics that remain.
+/// Applying a fix can let further checks run, so this repeats until nothing
+/// more can be fixed.
+fn fix_command(mut args: impl Iterator<Item = String>) {
+    let Some(filename) = args.next() else {
+        eprintln!("Usage: dash fix <file>");
+        process::exit(2);
+    };
+    let mut source = read_source(&filename);
+    let mut total = 0;
+    let remaining = loop {
+        let (errors, warnings) = diagnose(&source, false);
+        let diagnostics: Vec<Diagnostic> = errors.into_iter().chain(warnings).collect();
+        let (fixed, applied) = apply_fixes(&source, &diagnostics);
+        if applied == 0 {
+            break diagnostics;
+        }
+        source = fixed;
+        total += applied;
+    };
+
+    if total > 0 {
+        if let Err(e) = fs::write(&filename, &source) {
+            eprintln!("Error writing file '{}': {}", filename, e);
+            process::exit(1);
+        }
+    }
+    eprintln!("Applied {} fix{} to {}", total, if total == 1 { "" } else { "es" }, filename);
+    for diagnostic in &remaining {
+        eprintln!("{}", diagnostic);
+    }
+}
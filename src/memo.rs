@@ -0,0 +1,54 @@
+use std::collections::{HashMap, VecDeque};
+
+/// How many results `@memo` keeps when no size is given.
+pub const DEFAULT_MEMO_LIMIT: usize = 1000;
+
+/// The results of a `@memo` function, keyed by argument values.
+///
+/// Once `limit` results are stored, the oldest is dropped to make room for
+/// the next one.
+#[derive(Debug, Clone, Default)]
+pub struct MemoCache {
+    limit: usize,
+    results: HashMap<Vec<String>, String>,
+    /// Keys in the order they were stored, oldest first.
+    order: VecDeque<Vec<String>>,
+}
+
+impl MemoCache {
+    pub fn new(limit: usize) -> Self {
+        MemoCache {
+            limit,
+            ..MemoCache::default()
+        }
+    }
+
+    /// Returns the stored result for these arguments.
+    pub fn get(&self, args: &[String]) -> Option<&String> {
+        self.results.get(args)
+    }
+
+    /// Stores a result, evicting the oldest one if the cache is full.
+    pub fn insert(&mut self, args: Vec<String>, result: String) {
+        if self.limit == 0 || self.results.contains_key(&args) {
+            return;
+        }
+        if self.results.len() == self.limit {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+        self.order.push_back(args.clone());
+        self.results.insert(args, result);
+    }
+
+    /// Number of stored results.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns `true` if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use crate::ast::Permissions;
+
+/// The metadata header of a script: `key: value` entries between two `---`
+/// markers, before any code.
+///
+/// Entries are separated by commas or new lines. `name` and `description`
+/// are text, optionally in double quotes, and `requires` and `args` are
+/// lists in square brackets. Other keys are kept as text in `extra`, so
+/// hosts can define their own.
+///
+/// Each entry of `args` declares a command-line option: `env` is an option
+/// that takes a value and must be given, `region=us-east` one with a
+/// default, and `--dry-run` a flag that is `1` when given and `0` otherwise.
+/// `parse_args` turns command-line arguments into variables named after the
+/// options, with `-` replaced by `_`.
+///
+/// ```
+/// use dash_lang::meta::ScriptMeta;
+///
+/// let source = "--- name: deploy, requires: [fs, eval], args: [env] ---\nprint(env)\n";
+/// let meta = ScriptMeta::parse(source).unwrap().unwrap();
+/// assert_eq!(meta.name.as_deref(), Some("deploy"));
+/// assert_eq!(meta.requires, ["fs", "eval"]);
+/// assert_eq!(meta.args, ["env"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptMeta {
+    /// The script's name, for listing it.
+    pub name: Option<String>,
+    /// What the script does, for its usage text.
+    pub description: Option<String>,
+    /// Capabilities the script needs, such as `fs` or `eval`.
+    pub requires: Vec<String>,
+    /// Names of the arguments the script takes.
+    pub args: Vec<String>,
+    /// Any other entries, by key.
+    pub extra: HashMap<String, String>,
+}
+
+impl ScriptMeta {
+    /// Reads the metadata header at the start of `source`.
+    ///
+    /// # Returns
+    /// The metadata, `None` if there is no header, or a message describing
+    /// the first malformed entry.
+    pub fn parse(source: &str) -> Result<Option<ScriptMeta>, String> {
+        let Some((text, _)) = header(source) else {
+            return Ok(None);
+        };
+        let mut meta = ScriptMeta::default();
+        for entry in split_top_level(text, |c| c == ',' || c == '\n') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = entry.split_once(':') else {
+                return Err(format!("Invalid metadata entry '{}' (expected key: value)", entry));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "name" => meta.name = Some(unquote(value).to_string()),
+                "description" => meta.description = Some(unquote(value).to_string()),
+                "requires" => meta.requires = parse_list(key, value)?,
+                "args" => meta.args = parse_list(key, value)?,
+                _ => {
+                    meta.extra.insert(key.to_string(), unquote(value).to_string());
+                }
+            }
+        }
+        Ok(Some(meta))
+    }
+
+    /// Returns the entries of `requires` that `permissions` doesn't grant.
+    /// `fs` and `eval` correspond to `Permissions::allow_fs` and
+    /// `Permissions::allow_eval`; no other capability can be granted, so any
+    /// other requirement is always returned.
+    pub fn missing_permissions(&self, permissions: &Permissions) -> Vec<&str> {
+        self.requires
+            .iter()
+            .map(String::as_str)
+            .filter(|required| match *required {
+                "fs" => !permissions.allow_fs,
+                "eval" => !permissions.allow_eval,
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Parses command-line arguments against the options declared in `args`.
+    ///
+    /// Values can follow their option, as in `--env prod`, or be joined to
+    /// it, as in `--env=prod`.
+    ///
+    /// # Returns
+    /// A value for every declared option, keyed by variable name, or a
+    /// message naming an unknown option or a required one that is missing.
+    pub fn parse_args(&self, args: &[String]) -> Result<HashMap<String, String>, String> {
+        let specs: Vec<ArgSpec> = self.args.iter().map(|arg| ArgSpec::parse(arg)).collect();
+        let mut values = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                return Err(format!("Unexpected argument '{}'", arg));
+            };
+            let (option, joined) = match option.split_once('=') {
+                Some((option, value)) => (option, Some(value.to_string())),
+                None => (option, None),
+            };
+            let Some(spec) = specs.iter().find(|spec| spec.name == option) else {
+                return Err(format!("Unknown option '--{}'", option));
+            };
+            let value = if spec.flag {
+                if joined.is_some() {
+                    return Err(format!("Option '--{}' doesn't take a value", option));
+                }
+                "1".to_string()
+            } else {
+                match joined.or_else(|| args.next().cloned()) {
+                    Some(value) => value,
+                    None => return Err(format!("Option '--{}' needs a value", option)),
+                }
+            };
+            values.insert(spec.variable(), value);
+        }
+        for spec in &specs {
+            if values.contains_key(&spec.variable()) {
+                continue;
+            }
+            let value = match (&spec.default, spec.flag) {
+                (_, true) => "0".to_string(),
+                (Some(default), false) => default.clone(),
+                (None, false) => return Err(format!("Missing required option '--{}'", spec.name)),
+            };
+            values.insert(spec.variable(), value);
+        }
+        Ok(values)
+    }
+
+    /// Returns the names of the variables `parse_args` sets.
+    pub fn arg_variables(&self) -> Vec<String> {
+        self.args.iter().map(|arg| ArgSpec::parse(arg).variable()).collect()
+    }
+
+    /// Describes how to run the script, for a host's `--help`.
+    ///
+    /// # Arguments
+    /// * `command` - How the script is invoked, such as `dash deploy.dash`.
+    pub fn usage(&self, command: &str) -> String {
+        let mut usage = String::new();
+        match (&self.name, &self.description) {
+            (Some(name), Some(description)) => usage.push_str(&format!("{} - {}\n\n", name, description)),
+            (Some(text), None) | (None, Some(text)) => usage.push_str(&format!("{}\n\n", text)),
+            (None, None) => {}
+        }
+        usage.push_str(&format!("Usage: {}", command));
+        for spec in self.args.iter().map(|arg| ArgSpec::parse(arg)) {
+            match (&spec.default, spec.flag) {
+                (_, true) => usage.push_str(&format!(" [--{}]", spec.name)),
+                (Some(default), false) => {
+                    usage.push_str(&format!(" [--{} <{}> (default: {})]", spec.name, spec.name, default))
+                }
+                (None, false) => usage.push_str(&format!(" --{} <{}>", spec.name, spec.name)),
+            }
+        }
+        usage.push('\n');
+        usage
+    }
+}
+
+/// A command-line option declared in a header's `args`.
+struct ArgSpec {
+    /// The option's name, without the leading `--`.
+    name: String,
+    /// The value used when the option isn't given.
+    default: Option<String>,
+    /// Whether the option is a flag rather than taking a value.
+    flag: bool,
+}
+
+impl ArgSpec {
+    /// Reads an `args` entry: `name`, `name=default` or `--name`.
+    fn parse(entry: &str) -> ArgSpec {
+        if let Some(name) = entry.strip_prefix("--") {
+            return ArgSpec {
+                name: name.to_string(),
+                default: None,
+                flag: true,
+            };
+        }
+        let (name, default) = match entry.split_once('=') {
+            Some((name, default)) => (name.trim(), Some(unquote(default.trim()).to_string())),
+            None => (entry, None),
+        };
+        ArgSpec {
+            name: name.to_string(),
+            default,
+            flag: false,
+        }
+    }
+
+    /// The name of the variable the option's value is stored in.
+    fn variable(&self) -> String {
+        self.name.replace('-', "_")
+    }
+}
+
+/// Finds the metadata header at the start of `source`, which may follow
+/// blank lines but nothing else.
+///
+/// # Returns
+/// The text between the markers and the byte offset just past the closing
+/// marker, or `None` if there is no header.
+pub(crate) fn header(source: &str) -> Option<(&str, usize)> {
+    let start = source.len() - source.trim_start().len();
+    let body = source[start..].strip_prefix("---")?;
+    let len = body.find("---")?;
+    Some((&body[..len], start + 3 + len + 3))
+}
+
+/// Parses a `[a, b, c]` list.
+fn parse_list(key: &str, value: &str) -> Result<Vec<String>, String> {
+    let Some(items) = value.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) else {
+        return Err(format!("Metadata entry '{}' must be a list like [a, b]", key));
+    };
+    Ok(items
+        .split(',')
+        .map(|item| unquote(item.trim()).to_string())
+        .filter(|item| !item.is_empty())
+        .collect())
+}
+
+/// Splits `text` at separators that aren't inside square brackets or
+/// double quotes.
+fn split_top_level(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ if depth == 0 && is_separator(c) => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Removes one pair of surrounding double quotes, if present.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_multiline_header() {
+        let source = "---\nname: deploy\ndescription: \"Ship it, carefully\"\nrequires: [fs, exec]\nargs: [env, region]\nowner: ops\n---\nlet x = 1\n";
+        let meta = ScriptMeta::parse(source).unwrap().unwrap();
+        assert_eq!(meta.description.as_deref(), Some("Ship it, carefully"));
+        assert_eq!(meta.extra["owner"], "ops");
+        let permissions = Permissions {
+            allow_fs: true,
+            ..Permissions::default()
+        };
+        assert_eq!(meta.missing_permissions(&permissions), ["exec"]);
+        assert_eq!(
+            meta.usage("dash deploy.dash"),
+            "deploy - Ship it, carefully\n\nUsage: dash deploy.dash --env <env> --region <region>\n"
+        );
+
+        let args: Vec<String> = ["--env", "prod", "--region=eu"].map(String::from).to_vec();
+        let values = meta.parse_args(&args).unwrap();
+        assert_eq!(values["env"], "prod");
+        assert_eq!(values["region"], "eu");
+        assert_eq!(meta.parse_args(&[]).unwrap_err(), "Missing required option '--env'");
+
+        // The header isn't part of the program.
+        assert_eq!(parse(source).unwrap().len(), 1);
+        assert_eq!(ScriptMeta::parse("let x = 1").unwrap(), None);
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ast::{Context, Expr, FunctionTable, Stmt};
+use crate::cfg::apply_defines;
+use crate::eval::{catch_runtime_error, exec_stmt};
+use crate::include::resolve_includes;
+use crate::memo::MemoCache;
+use crate::parser::parse;
+
+/// Executes `import module` or `import module as alias`.
+///
+/// The module's functions become callable as `alias.name` (or `module.name`
+/// without an alias). Importing the same module under the same name again has
+/// no effect; reusing a namespace for a different module is an error.
+///
+/// # Arguments
+/// * `module` - The module name, resolved to `<module>.dash`.
+/// * `alias` - The namespace to import under, if different from the module name.
+/// * `ctx` - The importing context.
+pub fn import_namespace(module: &str, alias: Option<&str>, ctx: &mut Context) {
+    let namespace = alias.unwrap_or(module);
+    match ctx.namespaces.get(namespace) {
+        Some(existing) if existing == module => return,
+        Some(existing) => panic!(
+            "Import collision: namespace '{}' is already used by module '{}'",
+            namespace, existing
+        ),
+        None => {}
+    }
+
+    let (functions, memos) = load_module(module, ctx);
+    let names: HashSet<String> = functions.keys().cloned().collect();
+    for (name, cache) in memos {
+        ctx.memo.insert(format!("{}.{}", namespace, name), cache);
+    }
+    for (name, (params, body)) in functions {
+        let mut body = Rc::unwrap_or_clone(body);
+        qualify_calls(&mut body, &names, namespace);
+        ctx.functions
+            .insert(format!("{}.{}", namespace, name), (params, Rc::new(body)));
+    }
+    ctx.namespaces
+        .insert(namespace.to_string(), module.to_string());
+}
+
+/// Executes `from module import a, b`.
+///
+/// The named functions become callable without a prefix. The rest of the
+/// module is still imported under its own namespace, so imported functions can
+/// keep calling their helpers.
+///
+/// # Arguments
+/// * `module` - The module name, resolved to `<module>.dash`.
+/// * `names` - The functions to bring into scope.
+/// * `ctx` - The importing context.
+pub fn import_names(module: &str, names: &[String], ctx: &mut Context) {
+    import_namespace(module, None, ctx);
+    for name in names {
+        let qualified = format!("{}.{}", module, name);
+        let function = ctx
+            .functions
+            .get(&qualified)
+            .unwrap_or_else(|| panic!("Module '{}' has no function '{}'", module, name))
+            .clone();
+        if ctx.functions.contains_key(name) {
+            panic!(
+                "Import collision: '{}' from module '{}' is already defined",
+                name, module
+            );
+        }
+        ctx.functions.insert(name.clone(), function);
+        match ctx.memo.get(&qualified).cloned() {
+            Some(cache) => ctx.memo.insert(name.clone(), cache),
+            None => ctx.memo.remove(name),
+        };
+    }
+}
+
+/// Finds the file for a module.
+///
+/// A package dependency of that name wins; otherwise `ctx.base_dir` is
+/// searched first, followed by `ctx.module_paths` in order.
+///
+/// # Arguments
+/// * `module` - The module name, resolved to `<module>.dash`.
+/// * `ctx` - The context holding the search path.
+///
+/// # Returns
+/// The path of the first matching file, or `None` if no directory has one.
+pub fn resolve_module(module: &str, ctx: &Context) -> Option<PathBuf> {
+    resolve_module_from(module, &ctx.base_dir, ctx)
+}
+
+/// Like `resolve_module`, but searches `base_dir` in place of `ctx.base_dir`.
+pub(crate) fn resolve_module_from(module: &str, base_dir: &Path, ctx: &Context) -> Option<PathBuf> {
+    if let Some(entry) = ctx.packages.get(module) {
+        return Some(entry.clone());
+    }
+    let file = format!("{}.dash", module);
+    std::iter::once(base_dir)
+        .chain(ctx.module_paths.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(&file))
+        .find(|path| ctx.parsed_modules.contains_key(path) || path.is_file())
+}
+
+/// Returns the functions a module defines, loading it on first use, with
+/// the result caches of those defined with `@memo`.
+///
+/// A module is run only once per context; later imports of the same file
+/// reuse the cached functions. Modules already parsed by
+/// `Program::compile_project` are not read from disk again. The module runs in `ctx` so that settings such
+/// as logging carry over, but with its own empty set of variables,
+/// functions, namespaces and `@memo` caches, and with `base_dir` set to its
+/// own directory.
+/// The importer's state is restored afterwards. Importing a module that is
+/// still loading, directly or through other modules, is an error.
+fn load_module(module: &str, ctx: &mut Context) -> (FunctionTable, HashMap<String, MemoCache>) {
+    let path = resolve_module(module, ctx).unwrap_or_else(|| {
+        let searched: Vec<String> = std::iter::once(&ctx.base_dir)
+            .chain(&ctx.module_paths)
+            .map(|dir| dir.display().to_string())
+            .collect();
+        panic!(
+            "Cannot find module '{}' (searched: {})",
+            module,
+            searched.join(", ")
+        )
+    });
+    if let Some(functions) = ctx.modules.get(&path) {
+        return (functions.clone(), ctx.module_memos.get(&path).cloned().unwrap_or_default());
+    }
+    if let Some(start) = ctx.loading_modules.iter().position(|(loading, _)| *loading == path) {
+        let cycle: Vec<&str> = ctx.loading_modules[start..]
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .chain([module])
+            .collect();
+        panic!("Import cycle: {}", cycle.join(" -> "));
+    }
+
+    let program = match ctx.parsed_modules.get(&path) {
+        Some(program) => program.clone(),
+        None => {
+            let source = fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Cannot import module '{}' from {}: {}", module, path.display(), e))
+                .replace("\r\n", "\n");
+            let program =
+                parse(&source).unwrap_or_else(|e| panic!("Parse error in module '{}': {}", module, e));
+            let mut program = apply_defines(program, &ctx.defines);
+            let dir = path.parent().unwrap_or(Path::new(""));
+            resolve_includes(&mut program, dir).unwrap_or_else(|e| panic!("{} in module '{}'", e, module));
+            program
+        }
+    };
+
+    let module_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let base_dir = std::mem::replace(&mut ctx.base_dir, module_dir);
+    let variables = std::mem::take(&mut ctx.variables);
+    let functions = std::mem::take(&mut ctx.functions);
+    let namespaces = std::mem::take(&mut ctx.namespaces);
+    let memo = std::mem::take(&mut ctx.memo);
+    let depth = ctx.frames.len();
+    // A module that fails partway isn't loaded, whatever the error mode.
+    ctx.loading_modules.push((path.clone(), module.to_string()));
+    let result = catch_runtime_error(|| {
+        for stmt in &program {
+            exec_stmt(stmt, ctx);
+        }
+    });
+    ctx.loading_modules.pop();
+    // If the module failed inside one of its functions, the frames are left
+    // for the error report, and the importer's variables return when they
+    // are unwound.
+    let module_variables = match ctx.frames.get_mut(depth) {
+        Some(frame) => std::mem::replace(&mut frame.caller_variables, variables),
+        None => std::mem::replace(&mut ctx.variables, variables),
+    };
+    ctx.stats.release_scope(&module_variables);
+    ctx.base_dir = base_dir;
+    ctx.namespaces = namespaces;
+    let module_functions = std::mem::replace(&mut ctx.functions, functions);
+    let module_memos = std::mem::replace(&mut ctx.memo, memo);
+    if let Err(message) = result {
+        panic!("{}", message);
+    }
+
+    ctx.modules.insert(path.clone(), module_functions.clone());
+    ctx.module_memos.insert(path, module_memos.clone());
+    (module_functions, module_memos)
+}
+
+/// Rewrites calls to any of `names` within `stmts` to `namespace.name`.
+fn qualify_calls(stmts: &mut [Stmt], names: &HashSet<String>, namespace: &str) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Print(expr) | Stmt::Let(_, expr) | Stmt::Expr(expr) | Stmt::Return(expr) => {
+                qualify_expr(expr, names, namespace)
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                qualify_expr(condition, names, namespace);
+                qualify_calls(then_branch, names, namespace);
+                if let Some(else_branch) = else_branch {
+                    qualify_calls(else_branch, names, namespace);
+                }
+            }
+            Stmt::While { condition, body } => {
+                qualify_expr(condition, names, namespace);
+                qualify_calls(body, names, namespace);
+            }
+            Stmt::Fn { body, .. } | Stmt::Bench { body, .. } => qualify_calls(body, names, namespace),
+            Stmt::Call(name, args) => {
+                qualify_name(name, names, namespace);
+                for arg in args {
+                    qualify_expr(arg, names, namespace);
+                }
+            }
+            Stmt::Macro { args, body, .. } => {
+                for arg in args {
+                    qualify_expr(arg, names, namespace);
+                }
+                qualify_calls(body, names, namespace);
+            }
+            Stmt::Match { subject, arms } => {
+                qualify_expr(subject, names, namespace);
+                for arm in arms {
+                    if let Some(guard) = &mut arm.guard {
+                        qualify_expr(guard, names, namespace);
+                    }
+                    qualify_calls(&mut arm.body, names, namespace);
+                }
+            }
+            Stmt::Cfg {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                qualify_calls(then_branch, names, namespace);
+                if let Some(else_branch) = else_branch {
+                    qualify_calls(else_branch, names, namespace);
+                }
+            }
+            Stmt::Break | Stmt::Continue | Stmt::Import { .. } | Stmt::FromImport { .. } => {}
+        }
+    }
+}
+
+/// Rewrites calls to any of `names` within an expression.
+fn qualify_expr(expr: &mut Expr, names: &HashSet<String>, namespace: &str) {
+    match expr {
+        Expr::Call(name, args) => {
+            qualify_name(name, names, namespace);
+            for arg in args {
+                qualify_expr(arg, names, namespace);
+            }
+        }
+        Expr::Trace { expr, .. } => qualify_expr(expr, names, namespace),
+        Expr::Binary(left, _, right) => {
+            qualify_expr(left, names, namespace);
+            qualify_expr(right, names, namespace);
+        }
+        Expr::Chain(first, rest) => {
+            qualify_expr(first, names, namespace);
+            for (_, operand) in rest {
+                qualify_expr(operand, names, namespace);
+            }
+        }
+        Expr::Int(_) | Expr::Str(_) | Expr::Var(_) => {}
+    }
+}
+
+/// Prefixes a single function name with `namespace` if it is one of `names`.
+fn qualify_name(name: &mut String, names: &HashSet<String>, namespace: &str) {
+    if names.contains(name.as_str()) {
+        *name = format!("{}.{}", namespace, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::ErrorMode;
+    use crate::parser::run_with_context;
+
+    /// Creates a scratch directory containing the given module files.
+    fn module_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dash-module-{}-{}", test, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    const UTILS: &str = "fn helper(x) {\n  return x * 2\n}\nfn parse(x) {\n  return helper(x) + 1\n}\n";
+
+    #[test]
+    fn test_aliased_and_selective_imports() {
+        let mut ctx = Context {
+            base_dir: module_dir("alias", &[("utils.dash", UTILS)]),
+            ..Context::default()
+        };
+        run_with_context(
+            "import utils as u\nfrom utils import parse\nlet a = u.parse(1)\nlet b = parse(2)\n",
+            &mut ctx,
+        );
+        assert_eq!(ctx.variables["a"], "3");
+        assert_eq!(ctx.variables["b"], "5");
+        assert!(!ctx.functions.contains_key("helper"));
+    }
+
+    #[test]
+    fn test_import_collisions() {
+        let dir = module_dir("collide", &[("utils.dash", UTILS), ("other.dash", UTILS)]);
+        let mut ctx = Context {
+            base_dir: dir,
+            ..Context::default()
+        };
+        run_with_context("fn parse(x) {\n  return x\n}\n", &mut ctx);
+
+        let err = catch_runtime_error(|| import_names("utils", &["parse".to_string()], &mut ctx));
+        assert!(err.unwrap_err().contains("'parse' from module 'utils' is already defined"));
+
+        let err = catch_runtime_error(|| import_namespace("other", Some("utils"), &mut ctx));
+        assert!(err.unwrap_err().contains("namespace 'utils' is already used"));
+    }
+
+    #[test]
+    fn test_import_cycle_is_an_error() {
+        let dir = module_dir(
+            "cycle",
+            &[("a.dash", "import b\nfn one() {\n  return 1\n}\n"), ("b.dash", "import a\n")],
+        );
+        let mut ctx = Context {
+            base_dir: dir,
+            ..Context::default()
+        };
+        let err = catch_runtime_error(|| run_with_context("import a\n", &mut ctx)).unwrap_err();
+        assert_eq!(err, "Import cycle: a -> b -> a");
+        assert!(ctx.loading_modules.is_empty());
+    }
+
+    #[test]
+    fn test_failed_import_restores_the_importer() {
+        let lib = module_dir(
+            "failing",
+            &[
+                ("early.dash", "let m = 1\nprint(missing)\n"),
+                ("late.dash", "fn boom() {\n  let inner = 2\n  return missing\n}\nlet m = boom()\n"),
+            ],
+        );
+        let dir = module_dir("importer", &[]);
+        let mut ctx = Context {
+            base_dir: dir.clone(),
+            error_mode: ErrorMode::Continue,
+            ..Context::default()
+        };
+        ctx.add_module_path(&lib);
+        run_with_context("let keep = 1\nimport early\nimport late\nlet after = keep\n", &mut ctx);
+
+        assert_eq!(ctx.errors.len(), 2);
+        assert_eq!(ctx.variables["after"], "1");
+        assert!(!ctx.variables.contains_key("m"));
+        assert!(ctx.frames.is_empty());
+        assert_eq!(ctx.base_dir, dir);
+        assert!(ctx.functions.is_empty());
+    }
+
+    #[test]
+    fn test_memo_caches_stay_with_their_module() {
+        let dir = module_dir(
+            "memo",
+            &[("clock.dash", "@memo\nfn tick(n) {\n  return n\n}\nlet first = tick(1)\n")],
+        );
+        let mut ctx = Context {
+            base_dir: dir,
+            ..Context::default()
+        };
+        run_with_context("fn tick(n) {\n  return n + 1\n}\nimport clock\n", &mut ctx);
+        assert!(!ctx.memo.contains_key("tick"));
+        assert_eq!(ctx.memo["clock.tick"].len(), 1);
+
+        run_with_context("let a = clock.tick(2)\nlet b = tick(2)\n", &mut ctx);
+        assert_eq!(ctx.variables["a"], "2");
+        assert_eq!(ctx.variables["b"], "3");
+        assert_eq!(ctx.memo["clock.tick"].len(), 2);
+    }
+
+    #[test]
+    fn test_search_path_and_caching() {
+        let shared = module_dir("shared", &[("counter.dash", "print(\"loaded\")\nfn one() {\n  return 1\n}\n")]);
+        let mut ctx = Context {
+            base_dir: module_dir("empty", &[]),
+            ..Context::default()
+        };
+        ctx.add_module_path(&shared);
+        run_with_context("import counter\nimport counter as c\nlet x = c.one()\n", &mut ctx);
+
+        assert_eq!(ctx.variables["x"], "1");
+        assert_eq!(resolve_module("counter", &ctx), Some(shared.join("counter.dash")));
+        assert_eq!(ctx.modules.len(), 1);
+        assert!(resolve_module("missing", &ctx).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::ast::Context;
+use crate::eval::{catch_runtime_error, exec_program};
+use crate::interrupt;
+use crate::output::Capture;
+use crate::parser::parse;
+
+/// Runs the ```` ```dash ```` code blocks of a Markdown document in order,
+/// all in `ctx`, and puts what each block prints in an ```` ```output ````
+/// block right after it.
+///
+/// An output block already following a code block is replaced, so running a
+/// document again brings its outputs up to date. A block that prints
+/// nothing gets no output block. A block that fails to parse or stops with
+/// a runtime error gets the error message as its output, and later blocks
+/// still run. An interrupt stops the whole document.
+///
+/// # Arguments
+/// * `markdown` - The document.
+/// * `ctx` - The context the blocks run in.
+///
+/// # Returns
+/// The document with its output blocks updated.
+pub fn run_notebook(markdown: &str, ctx: &mut Context) -> String {
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(info) = fence_info(lines[i]) else {
+            push_line(&mut out, lines[i]);
+            i += 1;
+            continue;
+        };
+        let Some(end) = closing_fence(&lines, i + 1) else {
+            // An unclosed block runs to the end of the document.
+            for line in &lines[i..] {
+                push_line(&mut out, line);
+            }
+            break;
+        };
+        for line in &lines[i..=end] {
+            push_line(&mut out, line);
+        }
+        let code = lines[i + 1..end].join("\n");
+        i = end + 1;
+        if info != "dash" {
+            continue;
+        }
+
+        let output = run_block(&code, ctx);
+        let next = (i..lines.len()).find(|&j| !lines[j].trim().is_empty());
+        if let Some(start) = next.filter(|&j| fence_info(lines[j]) == Some("output")) {
+            if let Some(close) = closing_fence(&lines, start + 1) {
+                i = close + 1;
+            }
+        }
+        if !output.is_empty() {
+            out.push_str("\n```output\n");
+            out.push_str(&output);
+            if !output.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str("```\n");
+        }
+    }
+    out
+}
+
+/// Runs one code block, returning what it printed followed by any error.
+fn run_block(code: &str, ctx: &mut Context) -> String {
+    let program = match parse(code) {
+        Ok(program) => program,
+        Err(e) => return format!("Parse error: {}\n", e),
+    };
+    let capture = Capture::default();
+    let sink = std::mem::replace(&mut ctx.output.sink, Box::new(capture.clone()));
+    let result = catch_runtime_error(|| exec_program(&program, ctx));
+    ctx.output.sink = sink;
+
+    let mut output = capture.contents();
+    if let Err(message) = result {
+        if message == interrupt::INTERRUPTED {
+            panic!("{}", message);
+        }
+        ctx.unwind_to(0);
+        output.push_str(&format!("Runtime error: {}\n", message));
+    }
+    output
+}
+
+/// Returns the info string of an opening code fence, such as `dash` for
+/// ```` ```dash ````, or `None` if `line` isn't a fence.
+fn fence_info(line: &str) -> Option<&str> {
+    line.trim_start().strip_prefix("```").map(str::trim)
+}
+
+/// Finds the line closing a code block whose contents start at `from`.
+fn closing_fence(lines: &[&str], from: usize) -> Option<usize> {
+    (from..lines.len()).find(|&j| lines[j].trim() == "```")
+}
+
+/// Appends `line` and a line ending.
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line);
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outputs_are_inserted_and_replaced() {
+        let doc = "# Loops\n\n```dash\nlet x = 2\nprint(x * 3)\n```\n\n```output\nstale\n```\n\nText.\n\n```dash\nprint(x)\nprint(nope)\n```\n\n```dash\nlet quiet = 1\n```\n";
+        let mut ctx = Context::default();
+        let updated = run_notebook(doc, &mut ctx);
+        assert_eq!(
+            updated,
+            "# Loops\n\n```dash\nlet x = 2\nprint(x * 3)\n```\n\n```output\n6\n```\n\nText.\n\n```dash\nprint(x)\nprint(nope)\n```\n\n```output\n2\nRuntime error: Undefined variable: nope\n```\n\n```dash\nlet quiet = 1\n```\n"
+        );
+        // Running again changes nothing.
+        assert_eq!(run_notebook(&updated, &mut Context::default()), updated);
+    }
+}
@@ -0,0 +1,40 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Where `print` writes.
+pub struct Output {
+    /// Printed lines are written here. Defaults to stdout.
+    pub sink: Box<dyn Write>,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            sink: Box::new(io::stdout()),
+        }
+    }
+}
+
+/// An in-memory sink whose clones share one buffer, so output written
+/// through a clone given to `Output` can be read back afterwards.
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Capture {
+    /// Returns everything written so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
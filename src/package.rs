@@ -0,0 +1,437 @@
+//! Support for `dash.toml` package manifests.
+//!
+//! A manifest names the package's entry script and its dependencies:
+//!
+//! ```toml
+//! [package]
+//! name = "app"
+//! entry = "main.dash"
+//!
+//! [dependencies]
+//! utils = { path = "../utils" }
+//! strings = { git = "https://example.com/strings.git" }
+//! ```
+//!
+//! Each dependency is itself a directory containing a package. Scripts use it
+//! with `import <name>`, which loads the dependency's entry script, so a
+//! dependency's name must be one `import` accepts: letters, digits and `_`.
+//! The dependencies of dependencies are resolved too, and share one set of
+//! names with the package's own.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The file name of a package manifest.
+pub const MANIFEST_FILE: &str = "dash.toml";
+
+/// The entry script used when a manifest doesn't name one.
+pub const DEFAULT_ENTRY: &str = "main.dash";
+
+/// Where a dependency comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A directory, relative to the manifest.
+    Path(String),
+    /// A git repository, cloned into `.dash/deps/<name>`.
+    Git(String),
+}
+
+/// A single entry of the `[dependencies]` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+/// The contents of a `dash.toml` file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    /// The package name.
+    pub name: String,
+    /// The entry script, relative to the manifest.
+    pub entry: String,
+    /// The declared dependencies, in file order.
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Manifest {
+    /// Parses the text of a manifest.
+    ///
+    /// Only the subset of TOML used by manifests is understood: `[package]`
+    /// and `[dependencies]` tables, string values, and inline tables with a
+    /// single `path` or `git` key.
+    ///
+    /// # Returns
+    /// The manifest, or a message naming the offending line.
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let mut manifest = Manifest::default();
+        let mut section = String::new();
+        for (index, raw) in text.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| format!("{} line {}: {}", MANIFEST_FILE, index + 1, message);
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| error("expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            match section.as_str() {
+                "package" => {
+                    let value = parse_string(value).ok_or_else(|| error("expected a string"))?;
+                    match key {
+                        "name" => manifest.name = value,
+                        "entry" => manifest.entry = value,
+                        _ => return Err(error(&format!("unknown package key `{}`", key))),
+                    }
+                }
+                "dependencies" => {
+                    if !is_valid_name(key) {
+                        return Err(error(&format!("invalid dependency name `{}`", key)));
+                    }
+                    let source = parse_source(value).ok_or_else(|| {
+                        error("expected `{ path = \"...\" }` or `{ git = \"...\" }`")
+                    })?;
+                    manifest.dependencies.push(Dependency {
+                        name: key.to_string(),
+                        source,
+                    });
+                }
+                _ => return Err(error(&format!("unknown section `[{}]`", section))),
+            }
+        }
+        if manifest.entry.is_empty() {
+            manifest.entry = DEFAULT_ENTRY.to_string();
+        }
+        Ok(manifest)
+    }
+
+    /// Reads and parses the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        Manifest::parse(&text)
+    }
+}
+
+/// Looks for a `dash.toml` in `start` and each of its parent directories.
+///
+/// # Returns
+/// The path of the nearest manifest, if any.
+pub fn find_manifest(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(MANIFEST_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Makes every dependency of a package available locally, along with their
+/// own dependencies, and returns the script each one's `import` should load.
+///
+/// Path dependencies are used in place, relative to the manifest that names
+/// them. Git dependencies are cloned into `<root>/.dash/deps/<name>` the
+/// first time and reused afterwards. Dependencies are resolved level by
+/// level, so the package's own dependencies come first; a name used for two
+/// different sources is an error.
+///
+/// # Arguments
+/// * `manifest` - The package's manifest.
+/// * `root` - The directory containing the manifest.
+///
+/// # Returns
+/// A map from dependency name to its entry script.
+pub fn resolve_dependencies(
+    manifest: &Manifest,
+    root: &Path,
+) -> Result<HashMap<String, PathBuf>, String> {
+    let mut packages = HashMap::new();
+    // Where each resolved name came from: a git URL or a directory.
+    let mut origins: HashMap<String, String> = HashMap::new();
+    let mut pending: VecDeque<(Dependency, PathBuf)> = manifest
+        .dependencies
+        .iter()
+        .map(|dependency| (dependency.clone(), root.to_path_buf()))
+        .collect();
+    while let Some((dependency, base)) = pending.pop_front() {
+        if !is_valid_name(&dependency.name) {
+            return Err(format!("Invalid dependency name '{}'", dependency.name));
+        }
+        let (dir, origin) = match &dependency.source {
+            Source::Path(path) => {
+                let dir = base.join(path);
+                let origin = fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone());
+                (dir, origin.display().to_string())
+            }
+            Source::Git(url) => (root.join(".dash").join("deps").join(&dependency.name), url.clone()),
+        };
+        match origins.get(&dependency.name) {
+            Some(existing) if *existing == origin => continue,
+            Some(existing) => {
+                return Err(format!(
+                    "Dependency '{}' is required from both {} and {}",
+                    dependency.name, existing, origin
+                ))
+            }
+            None => {}
+        }
+        if let Source::Git(url) = &dependency.source {
+            if !dir.exists() {
+                let status = Command::new("git")
+                    .args(["clone", "--depth", "1", "--", url])
+                    .arg(&dir)
+                    .status()
+                    .map_err(|e| format!("Failed to run git: {}", e))?;
+                if !status.success() {
+                    return Err(format!("Failed to clone '{}' from {}", dependency.name, url));
+                }
+            }
+        }
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let entry = if manifest_path.is_file() {
+            let manifest = Manifest::load(&manifest_path)?;
+            pending.extend(manifest.dependencies.into_iter().map(|nested| (nested, dir.clone())));
+            manifest.entry
+        } else {
+            DEFAULT_ENTRY.to_string()
+        };
+        let entry = dir.join(entry);
+        if !entry.is_file() {
+            return Err(format!(
+                "Dependency '{}' has no entry script at {}",
+                dependency.name,
+                entry.display()
+            ));
+        }
+        origins.insert(dependency.name.clone(), origin);
+        packages.insert(dependency.name.clone(), entry);
+    }
+    Ok(packages)
+}
+
+/// Returns `true` if `name` can be used as a dependency name: a name that
+/// `import` accepts, which is also safe as a directory name.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Adds or replaces a dependency in the text of a manifest.
+///
+/// The rest of the file, including comments and formatting, is left as it is.
+/// A `[dependencies]` table is appended if the manifest has none.
+///
+/// # Arguments
+/// * `text` - The current manifest text.
+/// * `dependency` - The dependency to write.
+///
+/// # Returns
+/// The updated manifest text.
+pub fn add_dependency(text: &str, dependency: &Dependency) -> String {
+    let (key, value) = match &dependency.source {
+        Source::Path(path) => ("path", path),
+        Source::Git(url) => ("git", url),
+    };
+    let entry = format!("{} = {{ {} = {} }}", dependency.name, key, quote(value));
+
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let mut section = String::new();
+    let mut insert_at = None;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = strip_comment(line).trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        if section != "dependencies" {
+            continue;
+        }
+        if trimmed.split_once('=').map(|(k, _)| k.trim()) == Some(dependency.name.as_str()) {
+            lines[index] = entry;
+            return lines.join("\n") + "\n";
+        }
+        if !trimmed.is_empty() {
+            insert_at = Some(index + 1);
+        }
+    }
+
+    match insert_at {
+        Some(index) => lines.insert(index, entry),
+        None => match lines.iter().position(|l| l.trim() == "[dependencies]") {
+            Some(header) => lines.insert(header + 1, entry),
+            None => {
+                if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push("[dependencies]".to_string());
+                lines.push(entry);
+            }
+        },
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Removes a trailing `#` comment from a line, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parses a double-quoted TOML string with `\"` and `\\` escapes.
+fn parse_string(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(chars.next()?),
+            '"' => return None,
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// Parses a dependency's inline table, such as `{ path = "../utils" }`.
+fn parse_source(value: &str) -> Option<Source> {
+    let inner = value.strip_prefix('{')?.strip_suffix('}')?.trim();
+    let (key, value) = inner.split_once('=')?;
+    let value = parse_string(value.trim().trim_end_matches(',').trim())?;
+    match key.trim() {
+        "path" => Some(Source::Path(value)),
+        "git" => Some(Source::Git(value)),
+        _ => None,
+    }
+}
+
+/// Encodes a string as a TOML basic string.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# My app
+[package]
+name = "app"
+entry = "src/main.dash"
+
+[dependencies]
+utils = { path = "../utils" } # local checkout
+strings = { git = "https://example.com/strings.git" }
+"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        assert_eq!(manifest.name, "app");
+        assert_eq!(manifest.entry, "src/main.dash");
+        assert_eq!(
+            manifest.dependencies,
+            vec![
+                Dependency {
+                    name: "utils".to_string(),
+                    source: Source::Path("../utils".to_string()),
+                },
+                Dependency {
+                    name: "strings".to_string(),
+                    source: Source::Git("https://example.com/strings.git".to_string()),
+                },
+            ]
+        );
+        assert_eq!(Manifest::parse("[package]\nname = \"x\"\n").unwrap().entry, DEFAULT_ENTRY);
+        assert!(Manifest::parse("[package]\nname = x\n").unwrap_err().contains("line 2"));
+        let err = Manifest::parse("[dependencies]\n../../x = { path = \"x\" }\n").unwrap_err();
+        assert!(err.contains("invalid dependency name `../../x`"), "{}", err);
+    }
+
+    #[test]
+    fn test_add_dependency_preserves_file() {
+        let dependency = Dependency {
+            name: "json".to_string(),
+            source: Source::Path("vendor/json".to_string()),
+        };
+        let updated = add_dependency(MANIFEST, &dependency);
+        assert!(updated.starts_with("# My app\n"));
+        assert!(updated.ends_with(
+            "strings = { git = \"https://example.com/strings.git\" }\njson = { path = \"vendor/json\" }\n"
+        ));
+
+        let replaced = add_dependency(
+            &updated,
+            &Dependency {
+                name: "utils".to_string(),
+                source: Source::Git("https://example.com/utils.git".to_string()),
+            },
+        );
+        assert!(replaced.contains("utils = { git = \"https://example.com/utils.git\" }\n"));
+        assert_eq!(Manifest::parse(&replaced).unwrap().dependencies.len(), 3);
+
+        let fresh = add_dependency("[package]\nname = \"x\"\n", &dependency);
+        assert_eq!(fresh, "[package]\nname = \"x\"\n\n[dependencies]\njson = { path = \"vendor/json\" }\n");
+    }
+
+    #[test]
+    fn test_resolve_path_dependency() {
+        let root = std::env::temp_dir().join(format!("dash-package-{}", std::process::id()));
+        let dep = root.join("utils");
+        fs::create_dir_all(&dep).unwrap();
+        fs::write(dep.join(MANIFEST_FILE), "[package]\nname = \"utils\"\nentry = \"lib.dash\"\n").unwrap();
+        fs::write(dep.join("lib.dash"), "fn one() {\n  return 1\n}\n").unwrap();
+
+        let manifest = Manifest::parse("[dependencies]\nutils = { path = \"utils\" }\n").unwrap();
+        let packages = resolve_dependencies(&manifest, &root).unwrap();
+        assert_eq!(packages["utils"], dep.join("lib.dash"));
+    }
+
+    #[test]
+    fn test_resolve_nested_dependencies() {
+        let root = std::env::temp_dir().join(format!("dash-package-nested-{}", std::process::id()));
+        let files = [
+            ("app/dash.toml", "[dependencies]\nweb = { path = \"../web\" }\n"),
+            ("web/dash.toml", "[dependencies]\nstrings = { path = \"../strings\" }\n"),
+            ("web/main.dash", ""),
+            ("strings/main.dash", ""),
+            ("other/main.dash", ""),
+        ];
+        for (path, text) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        let app = root.join("app");
+        let manifest = Manifest::load(&app.join(MANIFEST_FILE)).unwrap();
+        let packages = resolve_dependencies(&manifest, &app).unwrap();
+        assert_eq!(packages.len(), 2);
+        assert!(packages["strings"].ends_with("strings/main.dash"));
+
+        let mut clashing = manifest.clone();
+        clashing.dependencies.push(Dependency {
+            name: "strings".to_string(),
+            source: Source::Path("../other".to_string()),
+        });
+        let err = resolve_dependencies(&clashing, &app).unwrap_err();
+        assert!(err.starts_with("Dependency 'strings' is required from both"), "{}", err);
+
+        let escaping = Manifest {
+            dependencies: vec![Dependency {
+                name: "../x".to_string(),
+                source: Source::Git("https://example.com/x.git".to_string()),
+            }],
+            ..Manifest::default()
+        };
+        assert_eq!(resolve_dependencies(&escaping, &app).unwrap_err(), "Invalid dependency name '../x'");
+    }
+}
@@ -1,35 +1,197 @@
 use pest::Parser;
 use pest_derive::Parser;
-use crate::eval::exec_stmt;
-use crate::ast::{Stmt, Expr, Op, Context};
+use crate::cfg::apply_defines;
+use crate::eval::{exec_program, run_exit_hooks};
+use crate::ast::{Stmt, Expr, Op, Context, MatchArm, Pattern};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::memo::DEFAULT_MEMO_LIMIT;
+use crate::meta;
 
 #[derive(Parser)]
 #[grammar = "dash.pest"]
 pub struct DashParser;
 
+/// The error returned when source code fails to parse.
+pub type ParseError = Box<pest::error::Error<Rule>>;
+
+/// Top-level statements paired with their `(start, end)` byte offsets.
+pub(crate) type RangedStmts = Vec<((usize, usize), Stmt)>;
+
 /// Parses and executes a source program written in the custom language.
 ///
 /// This function uses the Pest parser to convert the source string into an AST,
-/// then executes each statement in order using a fresh `Context`.
+/// then executes each statement in order using a fresh `Context`. Functions
+/// registered with `on_exit` run once the program finishes.
 ///
 /// # Arguments
 /// * `source` - A string slice containing the source code to run.
 pub fn run(source: &str) {
-    match DashParser::parse(Rule::program, source) {
-        Ok(mut pairs) => {
-            let pair = pairs.next().unwrap();
-            let ast = build_ast(pair.into_inner());
-            let mut ctx = Context::default();
-            for stmt in ast {
-                exec_stmt(&stmt, &mut ctx);
+    let mut ctx = Context::default();
+    run_with_context(source, &mut ctx);
+    run_exit_hooks(&mut ctx);
+}
+
+/// Parses and executes a source program using an existing `Context`.
+///
+/// Variables and functions defined by the program remain in `ctx` afterwards,
+/// which lets hosts inspect the results or run further code in the same scope.
+/// If the context has a prelude, it runs first. `@if` blocks are selected
+/// using `ctx.defines`. `include_str` calls are left to read their file at
+/// runtime, which needs `Permissions::allow_fs`; see `resolve_includes` for
+/// embedding the files before running instead.
+///
+/// # Arguments
+/// * `source` - A string slice containing the source code to run.
+/// * `ctx` - The execution context to run the program in.
+pub fn run_with_context(source: &str, ctx: &mut Context) {
+    if let Some(prelude) = ctx.prelude.clone() {
+        match parse(&prelude) {
+            Ok(ast) => exec_program(&apply_defines(ast, &ctx.defines), ctx),
+            Err(e) => {
+                println!("Parse error in prelude: {}", e);
+                return;
             }
         }
+    }
+    match parse(source) {
+        Ok(ast) => exec_program(&apply_defines(ast, &ctx.defines), ctx),
         Err(e) => {
             println!("Parse error: {}", e);
         }
     }
 }
 
+/// Parses a source program into its abstract syntax tree without executing it.
+///
+/// # Arguments
+/// * `source` - A string slice containing the source code to parse.
+///
+/// # Returns
+/// The program's statements, or the Pest error describing the first syntax error.
+pub fn parse(source: &str) -> Result<Vec<Stmt>, ParseError> {
+    let pair = DashParser::parse(Rule::program, source)
+        .map_err(Box::new)?
+        .next()
+        .unwrap();
+    Ok(build_ast(pair.into_inner()))
+}
+
+/// Parses a source program, also returning the byte range of each top-level
+/// statement.
+///
+/// # Arguments
+/// * `source` - A string slice containing the source code to parse.
+///
+/// # Returns
+/// The statements paired with their `(start, end)` byte offsets, or the Pest
+/// error describing the first syntax error.
+pub(crate) fn parse_with_ranges(source: &str) -> Result<RangedStmts, ParseError> {
+    let pair = DashParser::parse(Rule::program, source)
+        .map_err(Box::new)?
+        .next()
+        .unwrap();
+    Ok(pair
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::statement)
+        .map(|pair| {
+            let range = (pair.as_span().start(), pair.as_span().end());
+            (range, build_stmt(pair.into_inner()))
+        })
+        .collect())
+}
+
+/// Parses a source program, recovering from syntax errors so that every
+/// error in the file is reported rather than only the first.
+///
+/// After an error, parsing resumes on the next line, skipping further lines
+/// until any braces opened since the start of the broken statement are closed
+/// again, so the rest of a broken block isn't reported as separate errors.
+///
+/// # Arguments
+/// * `source` - A string slice containing the source code to parse.
+///
+/// # Returns
+/// The statements that parsed, and an error diagnostic for each syntax error.
+pub fn parse_with_diagnostics(source: &str) -> (Vec<Stmt>, Vec<Diagnostic>) {
+    let mut statements = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut pos = meta::header(source).map_or(0, |(_, end)| end);
+    loop {
+        pos += source[pos..].len() - source[pos..].trim_start().len();
+        if pos == source.len() {
+            break;
+        }
+        match DashParser::parse(Rule::statement, &source[pos..]) {
+            Ok(mut pairs) => {
+                let pair = pairs.next().unwrap();
+                pos += pair.as_span().end();
+                statements.push(build_stmt(pair.into_inner()));
+            }
+            Err(e) => {
+                let (start, end) = match e.location {
+                    pest::error::InputLocation::Pos(at) => (at, at),
+                    pest::error::InputLocation::Span(span) => span,
+                };
+                let span = pest::Span::new(source, pos + start, pos + end).unwrap();
+                diagnostics.push(
+                    let_with_equality(source, pos)
+                        .unwrap_or_else(|| Diagnostic::error(e.variant.message(), span.into())),
+                );
+                pos = skip_broken_statement(source, pos);
+            }
+        }
+    }
+    (statements, diagnostics)
+}
+
+/// Recognizes `let x == value` in a statement that failed to parse, returning
+/// a diagnostic that suggests `=` instead.
+fn let_with_equality(source: &str, start: usize) -> Option<Diagnostic> {
+    let rest = source[start..].strip_prefix("let")?;
+    let name_start = rest.len() - rest.trim_start().len();
+    let name_len = rest[name_start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len() - name_start);
+    let after_name = &rest[name_start + name_len..];
+    let op = start + 3 + name_start + name_len + (after_name.len() - after_name.trim_start().len());
+    if name_start == 0 || name_len == 0 || !source[op..].starts_with("==") {
+        return None;
+    }
+    let span = Span::from(pest::Span::new(source, op, op + 2).unwrap());
+    let message = "expected `=` in `let`, found `==`; did you mean `=`?";
+    Some(Diagnostic::error(message, span).with_fix(span, "="))
+}
+
+/// Returns the offset of the line after the statement starting at `start`,
+/// continuing past lines until the braces it opens are balanced.
+fn skip_broken_statement(source: &str, start: usize) -> usize {
+    let mut depth = 0i32;
+    let mut pos = start;
+    for line in source[start..].split_inclusive('\n') {
+        pos += line.len();
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+        if depth <= 0 {
+            break;
+        }
+    }
+    pos
+}
+
+/// Parses a single expression, such as a line typed into the REPL.
+///
+/// # Arguments
+/// * `source` - A string slice containing exactly one expression.
+///
+/// # Returns
+/// The parsed expression, or the Pest error describing the syntax error.
+pub fn parse_expr(source: &str) -> Result<Expr, ParseError> {
+    let pair = DashParser::parse(Rule::expr_input, source)
+        .map_err(Box::new)?
+        .next()
+        .unwrap();
+    Ok(build_expr(pair.into_inner().next().unwrap()))
+}
+
 /// Converts a sequence of Pest pairs into a list of statements (AST).
 ///
 /// Filters out non-statement rules and delegates to `build_stmt` for each.
@@ -72,6 +234,25 @@ fn build_stmt(mut pairs: pest::iterators::Pairs<Rule>) -> Stmt {
             let expr = build_expr(inner.next().unwrap());
             Stmt::Let(name, expr)
         }
+        Rule::bench_stmt => {
+            let mut inner = pair.into_inner();
+            let label = inner.next().unwrap().as_str();
+            Stmt::Bench {
+                label: label[1..label.len() - 1].to_string(),
+                body: build_block(inner.next().unwrap()),
+            }
+        }
+        Rule::cfg_stmt => {
+            let mut inner = pair.into_inner();
+            let flag = inner.next().unwrap().as_str().to_string();
+            let then_branch = build_block(inner.next().unwrap());
+            let else_branch = inner.next().map(build_block);
+            Stmt::Cfg {
+                flag,
+                then_branch,
+                else_branch,
+            }
+        }
         Rule::if_stmt => {
             let mut inner = pair.into_inner();
             let condition = build_expr(inner.next().unwrap());
@@ -92,28 +273,132 @@ fn build_stmt(mut pairs: pest::iterators::Pairs<Rule>) -> Stmt {
         Rule::break_stmt => Stmt::Break,
         Rule::continue_stmt => Stmt::Continue,
         Rule::fn_stmt => {
+            let mut doc_lines = Vec::new();
+            let mut memo = None;
+            let mut name = String::new();
+            let mut params = Vec::new();
+            let mut body = Vec::new();
+            for part in pair.into_inner() {
+                match part.as_rule() {
+                    Rule::doc_comment => {
+                        let line = &part.as_str()[3..];
+                        doc_lines.push(line.strip_prefix(' ').unwrap_or(line).to_string());
+                    }
+                    Rule::memo_attr => {
+                        let limit = part.into_inner().next().map(|n| n.as_str().parse().unwrap());
+                        memo = Some(limit.unwrap_or(DEFAULT_MEMO_LIMIT));
+                    }
+                    Rule::ident => name = part.as_str().to_string(),
+                    Rule::param_list => {
+                        params = part.into_inner().map(|p| p.as_str().to_string()).collect()
+                    }
+                    Rule::block => body = build_block(part),
+                    _ => unreachable!(),
+                }
+            }
+            let doc = (!doc_lines.is_empty()).then(|| doc_lines.join("\n"));
+            Stmt::Fn {
+                name,
+                params,
+                body,
+                doc,
+                memo,
+            }
+        }
+        Rule::macro_stmt => {
             let mut inner = pair.into_inner();
             let name = inner.next().unwrap().as_str().to_string();
-            let param_list = inner.next().unwrap();
-            let params = param_list
-                .into_inner()
-                .map(|p| p.as_str().to_string())
-                .collect();
-            let body = build_block(inner.next().unwrap());
-            Stmt::Fn { name, params, body }
-        }
-        Rule::call_stmt => {
-            let expr = build_expr(pair.into_inner().next().unwrap());
-            if let Expr::Call(name, args) = expr {
-                Stmt::Call(name, args)
-            } else {
-                panic!("Expected call expression in call_stmt");
+            let mut args = Vec::new();
+            let mut body = Vec::new();
+            for part in inner {
+                match part.as_rule() {
+                    Rule::arg_list => args = part.into_inner().map(build_expr).collect(),
+                    _ => body = build_block(part),
+                }
             }
+            Stmt::Macro { name, args, body }
         }
+        Rule::call_stmt => match build_expr(pair.into_inner().next().unwrap()) {
+            Expr::Call(name, args) => Stmt::Call(name, args),
+            // `trace(x)` on its own line.
+            expr => Stmt::Expr(expr),
+        },
         Rule::return_stmt => {
             let expr = build_expr(pair.into_inner().next().unwrap());
             Stmt::Return(expr)
         }
+        Rule::import_stmt => {
+            let mut inner = pair.into_inner();
+            let module = inner.next().unwrap().as_str().to_string();
+            let alias = inner.next().map(|p| p.as_str().to_string());
+            Stmt::Import { module, alias }
+        }
+        Rule::from_stmt => {
+            let mut inner = pair.into_inner();
+            let module = inner.next().unwrap().as_str().to_string();
+            let names = inner.map(|p| p.as_str().to_string()).collect();
+            Stmt::FromImport { module, names }
+        }
+        Rule::match_stmt => {
+            let mut inner = pair.into_inner();
+            let subject = build_expr(inner.next().unwrap());
+            let arms = inner.map(build_match_arm).collect();
+            Stmt::Match { subject, arms }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Builds a single `match` arm from its Pest pair representation.
+///
+/// An arm consists of one or more alternative patterns, an optional guard
+/// expression, and the block executed when the arm is taken.
+///
+/// # Arguments
+/// * `pair` - A Pest pair representing a match arm.
+///
+/// # Returns
+/// A `MatchArm` describing the arm.
+fn build_match_arm(pair: pest::iterators::Pair<Rule>) -> MatchArm {
+    let mut span = Span::from(pair.as_span());
+    let mut patterns = Vec::new();
+    let mut guard = None;
+    let mut body = Vec::new();
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::pattern => {
+                span.end = part.as_span().end();
+                patterns.push(build_pattern(part.into_inner().next().unwrap()));
+            }
+            Rule::guard => guard = Some(build_expr(part.into_inner().next().unwrap())),
+            Rule::block => body = build_block(part),
+            _ => unreachable!(),
+        }
+    }
+    MatchArm {
+        patterns,
+        guard,
+        body,
+        span,
+    }
+}
+
+/// Builds a `match` pattern from its Pest pair representation.
+///
+/// # Arguments
+/// * `pair` - A Pest pair representing a literal, identifier, or wildcard.
+///
+/// # Returns
+/// The corresponding `Pattern`.
+fn build_pattern(pair: pest::iterators::Pair<Rule>) -> Pattern {
+    match pair.as_rule() {
+        Rule::wildcard => Pattern::Wildcard,
+        Rule::number => Pattern::Int(pair.as_str().parse().unwrap()),
+        Rule::string => {
+            let s = pair.as_str();
+            Pattern::Str(s[1..s.len() - 1].to_string())
+        }
+        Rule::ident => Pattern::Bind(pair.as_str().to_string()),
         _ => unreachable!(),
     }
 }
@@ -150,6 +435,7 @@ fn build_expr(pair: pest::iterators::Pair<Rule>) -> Expr {
                 let op = match op_pair.as_str() {
                     "*" => Op::Mul,
                     "/" => Op::Div,
+                    "//" => Op::FloorDiv,
                     _ => panic!("Unexpected operator in term: {:?}", op_pair.as_str()),
                 };
                 let right = build_expr(inner.next().unwrap());
@@ -167,7 +453,8 @@ fn build_expr(pair: pest::iterators::Pair<Rule>) -> Expr {
         Rule::comparison => {
             let mut inner = pair.into_inner();
             let left = build_expr(inner.next().unwrap());
-            if let Some(op_pair) = inner.next() {
+            let mut rest = Vec::new();
+            while let Some(op_pair) = inner.next() {
                 let right = build_expr(inner.next().unwrap());
                 let op = match op_pair.as_str() {
                     ">" => Op::Greater,
@@ -178,14 +465,35 @@ fn build_expr(pair: pest::iterators::Pair<Rule>) -> Expr {
                     "!=" => Op::NotEqual,
                     _ => unreachable!(),
                 };
-                Expr::Binary(Box::new(left), op, Box::new(right))
-            } else {
-                left
+                rest.push((op, right));
+            }
+            // `a < b < c` becomes a chain so that `b` is evaluated only once.
+            match rest.len() {
+                0 => left,
+                1 => {
+                    let (op, right) = rest.pop().unwrap();
+                    Expr::Binary(Box::new(left), op, Box::new(right))
+                }
+                _ => Expr::Chain(Box::new(left), rest),
             }
         }
         Rule::call_expr => {
+            let (line, column) = pair.line_col();
             let mut inner = pair.into_inner();
             let name = inner.next().unwrap().as_str().to_string();
+            if name == "trace" {
+                if let Some(arg_list) = inner.clone().next() {
+                    let mut args = arg_list.into_inner();
+                    if let (Some(arg), None) = (args.next(), args.next()) {
+                        return Expr::Trace {
+                            source: arg.as_str().trim().to_string(),
+                            expr: Box::new(build_expr(arg)),
+                            line,
+                            column,
+                        };
+                    }
+                }
+            }
             let args = if let Some(arg_list) = inner.next() {
                 arg_list.into_inner().map(build_expr).collect()
             } else {
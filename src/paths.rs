@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Finds the files and directories matching a glob pattern, sorted.
+///
+/// In each path component, `*` matches any run of characters and `?` a
+/// single one; neither matches a leading `.`, so hidden files are only found
+/// by patterns that spell out the dot. A component that is exactly `**`
+/// matches any number of directories, including none. Relative patterns are
+/// resolved against the current directory.
+pub fn glob(pattern: &str) -> Vec<PathBuf> {
+    let absolute = Path::new(pattern).is_absolute();
+    let mut matches = vec![if absolute { PathBuf::from("/") } else { PathBuf::new() }];
+    for component in pattern.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for path in &matches {
+            if component == "**" {
+                collect_dirs(path, &mut next);
+            } else if !component.contains(['*', '?']) {
+                let candidate = path.join(component);
+                if candidate.exists() {
+                    next.push(candidate);
+                }
+            } else {
+                for entry in read_dir(path) {
+                    let name = entry.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    if wildcard_match(component, &name) {
+                        next.push(entry);
+                    }
+                }
+            }
+        }
+        next.sort();
+        next.dedup();
+        matches = next;
+    }
+    matches.retain(|path| !path.as_os_str().is_empty());
+    matches
+}
+
+/// Adds `dir` and every directory below it, skipping hidden ones.
+fn collect_dirs(dir: &Path, out: &mut Vec<PathBuf>) {
+    out.push(dir.to_path_buf());
+    for entry in read_dir(dir) {
+        let hidden = entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if entry.is_dir() && !hidden {
+            collect_dirs(&entry, out);
+        }
+    }
+}
+
+/// Lists a directory's entries, treating an empty path as the current
+/// directory and unreadable directories as empty.
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+    let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let Ok(entries) = fs::read_dir(listed) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| dir.join(entry.file_name()))
+        .collect()
+}
+
+/// Matches a file name against a pattern of literal characters, `*` and `?`.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Positions to resume from after the most recent `*`.
+    let (mut p, mut n, mut star) = (0, 0, None);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(wildcard_match("*.txt", "notes.txt"));
+        assert!(wildcard_match("data-??.csv", "data-01.csv"));
+        assert!(!wildcard_match("data-??.csv", "data-1.csv"));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("*", ".hidden"));
+        assert!(wildcard_match(".*", ".hidden"));
+    }
+
+    #[test]
+    fn test_glob_in_directories() {
+        let root = std::env::temp_dir().join(format!("dash-glob-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        for file in ["a.dash", "src/b.dash", "src/nested/c.dash", "src/notes.txt", ".git/d.dash"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let pattern = format!("{}/**/*.dash", root.display());
+        let found: Vec<PathBuf> = glob(&pattern)
+            .into_iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect();
+        let expected: Vec<PathBuf> = ["a.dash", "src/b.dash", "src/nested/c.dash"].iter().map(PathBuf::from).collect();
+        assert_eq!(found, expected);
+        assert_eq!(glob(&format!("{}/src/*.txt", root.display())), [root.join("src/notes.txt")]);
+        assert!(glob(&format!("{}/missing/*", root.display())).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_filesystem_builtins_need_permission() {
+        use crate::ast::Context;
+        use crate::eval::{catch_runtime_error, eval_expr};
+        use crate::parser::parse_expr;
+
+        let mut ctx = Context::default();
+        for call in ["glob(\"*\")", "absolute(\"notes.txt\")"] {
+            let expr = parse_expr(call).unwrap();
+            let err = catch_runtime_error(|| eval_expr(&expr, &mut ctx)).unwrap_err();
+            assert!(err.contains("Permissions::allow_fs"), "{}", err);
+        }
+
+        ctx.permissions.allow_fs = true;
+        let expr = parse_expr("absolute(\"notes.txt\")").unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(eval_expr(&expr, &mut ctx), cwd.join("notes.txt").display().to_string());
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::ast::{Context, Stmt};
+use crate::eval::exec_program;
+use crate::include::resolve_includes;
+use crate::module::resolve_module_from;
+use crate::parser::parse;
+
+/// A script together with every module it imports, parsed ahead of time.
+///
+/// Parsing happens on several threads. Execution is still sequential and
+/// follows the same rules as running the entry script directly.
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// The path of the entry script.
+    pub path: PathBuf,
+    /// The entry script's statements.
+    pub entry: Vec<Stmt>,
+    /// Every module reachable through `import`, keyed by file path.
+    pub modules: HashMap<PathBuf, Vec<Stmt>>,
+}
+
+impl Program {
+    /// Parses an entry script and all modules it imports, directly or
+    /// indirectly, resolving imports the way a default `Context` would.
+    ///
+    /// # Arguments
+    /// * `entry` - The path of the entry script.
+    ///
+    /// # Returns
+    /// The compiled program, or a message describing the first file that
+    /// couldn't be read or parsed.
+    pub fn compile_project(entry: impl AsRef<Path>) -> Result<Program, String> {
+        Self::compile_project_with(entry, &Context::default())
+    }
+
+    /// Like `compile_project`, but resolves imports using the module search
+    /// path and packages configured on `ctx`.
+    pub fn compile_project_with(entry: impl AsRef<Path>, ctx: &Context) -> Result<Program, String> {
+        let path = entry.as_ref().to_path_buf();
+        let mut parsed = HashMap::new();
+        let mut seen = HashSet::from([path.clone()]);
+        let mut wave = vec![path.clone()];
+
+        // Each wave parses the files discovered by the previous one.
+        while !wave.is_empty() {
+            let mut next = Vec::new();
+            for (file, program) in parse_files(&wave)? {
+                let dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+                let mut imports = Vec::new();
+                collect_imports(&program, &mut imports);
+                for module in imports {
+                    // Unresolvable imports are left to fail at runtime, where
+                    // they may never be reached.
+                    if let Some(found) = resolve_module_from(&module, &dir, ctx) {
+                        if seen.insert(found.clone()) {
+                            next.push(found);
+                        }
+                    }
+                }
+                parsed.insert(file, program);
+            }
+            wave = next;
+        }
+
+        let entry = parsed.remove(&path).unwrap();
+        Ok(Program {
+            path,
+            entry,
+            modules: parsed,
+        })
+    }
+
+    /// Executes the program in `ctx`, using the pre-parsed modules for imports.
+    pub fn run(&self, ctx: &mut Context) {
+        if let Some(dir) = self.path.parent() {
+            ctx.base_dir = dir.to_path_buf();
+        }
+        ctx.parsed_modules
+            .extend(self.modules.iter().map(|(path, program)| (path.clone(), program.clone())));
+        exec_program(&self.entry, ctx);
+    }
+}
+
+/// Reads and parses files on a pool of scoped threads.
+fn parse_files(files: &[PathBuf]) -> Result<Vec<(PathBuf, Vec<Stmt>)>, String> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+
+    let results: Vec<Result<(PathBuf, Vec<Stmt>), String>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(index) else {
+                            break;
+                        };
+                        done.push(parse_file(file).map(|program| (file.clone(), program)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    results.into_iter().collect()
+}
+
+/// Reads and parses a single file.
+fn parse_file(path: &Path) -> Result<Vec<Stmt>, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading file '{}': {}", path.display(), e))?
+        .replace("\r\n", "\n");
+    let mut program = parse(&source).map_err(|e| format!("Parse error in {}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    resolve_includes(&mut program, dir).map_err(|e| format!("{} in {}", e, path.display()))?;
+    Ok(program)
+}
+
+/// Collects the names of all modules imported anywhere in `stmts`.
+fn collect_imports(stmts: &[Stmt], imports: &mut Vec<String>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Import { module, .. } | Stmt::FromImport { module, .. } => {
+                imports.push(module.clone())
+            }
+            Stmt::If {
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Stmt::Cfg {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect_imports(then_branch, imports);
+                if let Some(else_branch) = else_branch {
+                    collect_imports(else_branch, imports);
+                }
+            }
+            Stmt::While { body, .. }
+            | Stmt::Fn { body, .. }
+            | Stmt::Bench { body, .. }
+            | Stmt::Macro { body, .. } => collect_imports(body, imports),
+            Stmt::Match { arms, .. } => {
+                for arm in arms {
+                    collect_imports(&arm.body, imports);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_project_parses_imports_transitively() {
+        let dir = std::env::temp_dir().join(format!("dash-program-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.dash"), "import a\nlet x = a.f()\n").unwrap();
+        fs::write(dir.join("a.dash"), "import b\nfn f() {\n  return b.g() + 1\n}\n").unwrap();
+        fs::write(dir.join("b.dash"), "fn g() {\n  return 41\n}\n").unwrap();
+
+        let program = Program::compile_project(dir.join("main.dash")).unwrap();
+        assert_eq!(program.modules.len(), 2);
+
+        // The files are no longer needed once compiled.
+        fs::remove_file(dir.join("b.dash")).unwrap();
+        let mut ctx = Context::default();
+        program.run(&mut ctx);
+        assert_eq!(ctx.variables["x"], "42");
+    }
+}
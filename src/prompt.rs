@@ -0,0 +1,395 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time::Instant;
+
+use crate::ast::Context;
+use crate::eval::{catch_runtime_error, check_interrupt};
+use crate::interrupt::{self, INTERRUPTED, POLL_INTERVAL};
+
+/// Where the prompt builtins (`confirm`, `select`, `password`) read answers
+/// from and write their questions to, and where `read_line` and
+/// `read_all_stdin` read from.
+pub struct Input {
+    /// Answers and other input, one per line. Defaults to stdin, which the
+    /// builtins stop waiting on if the script is interrupted or its deadline
+    /// passes; other sources are read without checking.
+    pub source: Box<dyn BufRead>,
+    /// Questions are written here. Defaults to stdout.
+    pub prompts: Box<dyn Write>,
+    /// Whether `source` is the terminal, so `password` can turn off echo.
+    pub terminal: bool,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input {
+            source: Box::new(StdinReader::default()),
+            prompts: Box::new(io::stdout()),
+            terminal: io::stdin().is_terminal(),
+        }
+    }
+}
+
+impl Input {
+    /// Writes `prompt` and reads one line, without its line ending.
+    ///
+    /// Panics at the end of input, since the question can't be answered.
+    fn ask(&mut self, prompt: &str) -> String {
+        let _ = write!(self.prompts, "{}", prompt);
+        let _ = self.prompts.flush();
+        let mut line = String::new();
+        match self.source.read_line(&mut line) {
+            Ok(0) => panic!("No input left to answer '{}'", prompt.trim_end()),
+            Ok(_) => line.trim_end_matches(['\r', '\n']).to_string(),
+            Err(e) => read_failed(e),
+        }
+    }
+
+    /// Reads the next line, without its line ending.
+    ///
+    /// # Returns
+    /// The line, or `None` at the end of input.
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.source.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+            Err(e) => read_failed(e),
+        }
+    }
+
+    /// Returns `true` if there is input left to read.
+    pub fn has_line(&mut self) -> bool {
+        match self.source.fill_buf() {
+            Ok(buf) => !buf.is_empty(),
+            Err(e) => read_failed(e),
+        }
+    }
+
+    /// Reads everything up to the end of input.
+    pub fn read_all(&mut self) -> String {
+        let mut text = String::new();
+        if let Err(e) = self.source.read_to_string(&mut text) {
+            read_failed(e);
+        }
+        text
+    }
+
+    /// Asks a yes/no question until it gets `y`, `yes`, `n` or `no`.
+    pub fn confirm(&mut self, message: &str) -> bool {
+        loop {
+            match self.ask(&format!("{} [y/n] ", message)).trim().to_lowercase().as_str() {
+                "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => {}
+            }
+        }
+    }
+
+    /// Lists numbered options and asks until one is picked, by number or by
+    /// its exact text.
+    pub fn select(&mut self, message: &str, options: &[String]) -> String {
+        let _ = writeln!(self.prompts, "{}", message);
+        for (i, option) in options.iter().enumerate() {
+            let _ = writeln!(self.prompts, "  {}) {}", i + 1, option);
+        }
+        loop {
+            let answer = self.ask(&format!("Choose 1-{}: ", options.len()));
+            let answer = answer.trim();
+            let by_number = answer.parse::<usize>().ok().and_then(|n| options.get(n.wrapping_sub(1)));
+            if let Some(option) = by_number.or_else(|| options.iter().find(|o| *o == answer)) {
+                return option.clone();
+            }
+        }
+    }
+
+    /// Asks for a line without echoing what is typed, when the answer is
+    /// typed at a terminal.
+    pub fn password(&mut self, message: &str) -> String {
+        if !self.terminal {
+            return self.ask(&format!("{} ", message));
+        }
+        set_echo(false);
+        let answer = crate::eval::catch_runtime_error(|| self.ask(&format!("{} ", message)));
+        set_echo(true);
+        let _ = writeln!(self.prompts);
+        answer.unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Runs `read` on the input of `ctx`, which may wait for stdin.
+///
+/// While it waits, an interrupt or a passed deadline stops the script as it
+/// would between statements: the `on_interrupt` hooks run and the builtin
+/// fails with an `INTERRUPTED` error.
+pub(crate) fn read_input<R>(ctx: &mut Context, read: impl FnOnce(&mut Input) -> R) -> R {
+    let watch = Watch {
+        interrupt: ctx.interrupt.clone(),
+        deadline: ctx.deadline,
+    };
+    let outer = WATCH.with(|current| current.replace(Some(watch)));
+    let result = catch_runtime_error(|| read(&mut ctx.input));
+    WATCH.with(|current| *current.borrow_mut() = outer);
+    match result {
+        Ok(value) => value,
+        Err(message) => {
+            if message == INTERRUPTED {
+                check_interrupt(ctx);
+            }
+            panic!("{}", message)
+        }
+    }
+}
+
+/// Panics for a failed read, with `INTERRUPTED` if the script was
+/// interrupted while waiting.
+fn read_failed(e: io::Error) -> ! {
+    if e.get_ref().is_some_and(|inner| inner.is::<Cancelled>()) {
+        panic!("{}", INTERRUPTED);
+    }
+    panic!("Error reading input: {}", e)
+}
+
+/// What `StdinReader` checks while it waits, for the script reading on
+/// this thread.
+struct Watch {
+    interrupt: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl Watch {
+    /// Whether the script has been interrupted, without clearing the flags
+    /// `check_interrupt` goes on to handle.
+    fn stopped(&self) -> bool {
+        self.interrupt.load(Ordering::SeqCst)
+            || interrupt::signal_pending()
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+thread_local! {
+    /// Set by `read_input` while a builtin reads.
+    static WATCH: RefCell<Option<Watch>> = const { RefCell::new(None) };
+}
+
+/// The error a `StdinReader` gives up with when the script is interrupted.
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(INTERRUPTED)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Stdin, read a chunk at a time by a helper thread so that a script can
+/// stop waiting for it.
+///
+/// A read blocked on the real stdin can't be abandoned, so the helper does
+/// the blocking and hands chunks over a channel, which can be waited on with
+/// a timeout. Only one chunk is asked for at a time; one asked for by a
+/// script that was interrupted goes to the next read instead.
+#[derive(Default)]
+struct StdinReader {
+    chunk: Vec<u8>,
+    consumed: usize,
+}
+
+/// The helper thread shared by every `StdinReader`.
+struct Helper {
+    requests: Sender<()>,
+    chunks: Receiver<io::Result<Vec<u8>>>,
+    /// Whether a chunk has been asked for and not yet received.
+    pending: bool,
+}
+
+static HELPER: OnceLock<Mutex<Helper>> = OnceLock::new();
+
+fn helper() -> MutexGuard<'static, Helper> {
+    HELPER
+        .get_or_init(|| {
+            let (requests, wanted) = mpsc::channel::<()>();
+            let (sender, chunks) = mpsc::channel();
+            thread::spawn(move || {
+                let mut stdin = io::stdin();
+                for () in wanted {
+                    let mut chunk = vec![0; 8 * 1024];
+                    let read = stdin.read(&mut chunk).map(|len| {
+                        chunk.truncate(len);
+                        chunk
+                    });
+                    if sender.send(read).is_err() {
+                        return;
+                    }
+                }
+            });
+            Mutex::new(Helper {
+                requests,
+                chunks,
+                pending: false,
+            })
+        })
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+impl StdinReader {
+    /// Waits for the next chunk of stdin, which is empty at the end of input.
+    fn next_chunk() -> io::Result<Vec<u8>> {
+        let mut helper = helper();
+        if !helper.pending {
+            let _ = helper.requests.send(());
+            helper.pending = true;
+        }
+        loop {
+            match helper.chunks.recv_timeout(POLL_INTERVAL) {
+                Ok(chunk) => {
+                    helper.pending = false;
+                    return chunk;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let stopped = WATCH.with(|watch| watch.borrow().as_ref().is_some_and(Watch::stopped));
+                    if stopped {
+                        return Err(io::Error::other(Cancelled));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(Vec::new()),
+            }
+        }
+    }
+}
+
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for StdinReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.consumed == self.chunk.len() {
+            self.chunk = Self::next_chunk()?;
+            self.consumed = 0;
+        }
+        Ok(&self.chunk[self.consumed..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed = (self.consumed + amount).min(self.chunk.len());
+    }
+}
+
+/// Turns terminal echo on or off with `stty`. Where `stty` isn't
+/// available, echo stays on.
+fn set_echo(on: bool) {
+    let _ = Command::new("stty").arg(if on { "echo" } else { "-echo" }).status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Clone, Default)]
+    struct Capture(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_prompts_retry_until_answered() {
+        let prompts = Capture::default();
+        let mut input = Input {
+            source: Box::new(io::Cursor::new("maybe\nYes\n4\nblue\nhunter2\n")),
+            prompts: Box::new(prompts.clone()),
+            terminal: false,
+        };
+        assert!(input.confirm("Deploy?"));
+        let colors = ["red".to_string(), "blue".to_string()];
+        assert_eq!(input.select("Color?", &colors), "blue");
+        assert_eq!(input.password("Password:"), "hunter2");
+
+        let written = String::from_utf8(prompts.0.borrow().clone()).unwrap();
+        assert_eq!(
+            written,
+            "Deploy? [y/n] Deploy? [y/n] Color?\n  1) red\n  2) blue\nChoose 1-2: Choose 1-2: Password: "
+        );
+        let err = crate::eval::catch_runtime_error(|| input.confirm("Again?")).unwrap_err();
+        assert!(err.contains("No input left"));
+    }
+
+    #[test]
+    fn test_read_lines_until_end_of_input() {
+        let mut input = Input {
+            source: Box::new(io::Cursor::new("GET /\r\n\nPOST /login\nrest\nof it")),
+            prompts: Box::new(io::sink()),
+            terminal: false,
+        };
+        assert_eq!(input.read_line().as_deref(), Some("GET /"));
+        assert_eq!(input.read_line().as_deref(), Some(""));
+        assert!(input.has_line());
+        assert_eq!(input.read_line().as_deref(), Some("POST /login"));
+        assert_eq!(input.read_all(), "rest\nof it");
+        assert!(!input.has_line());
+        assert_eq!(input.read_line(), None);
+    }
+
+    /// Waits like `StdinReader` for input that never comes.
+    struct Stalled;
+
+    impl Read for Stalled {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            unreachable!()
+        }
+    }
+
+    impl BufRead for Stalled {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            loop {
+                if WATCH.with(|watch| watch.borrow().as_ref().is_some_and(Watch::stopped)) {
+                    return Err(io::Error::other(Cancelled));
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        fn consume(&mut self, _: usize) {}
+    }
+
+    #[test]
+    fn test_reads_stop_at_the_deadline() {
+        let mut ctx = Context::default();
+        ctx.input.source = Box::new(Stalled);
+        ctx.deadline = Some(Instant::now() + std::time::Duration::from_millis(50));
+        let err = catch_runtime_error(|| read_input(&mut ctx, Input::read_line)).unwrap_err();
+        assert_eq!(err, INTERRUPTED);
+        assert!(WATCH.with(|watch| watch.borrow().is_none()));
+
+        ctx.deadline = None;
+        ctx.interrupt.store(true, Ordering::SeqCst);
+        let err = catch_runtime_error(|| read_input(&mut ctx, Input::has_line)).unwrap_err();
+        assert_eq!(err, INTERRUPTED);
+        assert!(!ctx.interrupt.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// The random number generator behind the random-id builtins.
+///
+/// It is a small, fast generator (SplitMix64), not a cryptographic one: ids
+/// it produces are unique enough for file names and records, but shouldn't
+/// be used as secrets. A context gets a randomly seeded generator by
+/// default; seed it with `ContextBuilder::seed` for repeatable runs.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator that always produces the same sequence for `seed`.
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Creates a generator seeded from the operating system's randomness.
+    pub fn from_entropy() -> Self {
+        Rng::new(RandomState::new().build_hasher().finish())
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fills `bytes` with random bytes.
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let random = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::from_entropy()
+    }
+}
+
+/// Characters used by `nanoid`: 64 URL-safe symbols, so each is 6 random bits.
+const NANOID_ALPHABET: &[u8; 64] = b"useandom-26T198340PX75pxJACKVERYMINDBUSHWOLF_GQZbfghjklqvwyzrict";
+
+/// Generates a random (version 4) UUID such as
+/// `4b1f0c8e-3d2a-4f6b-9c1e-7a5d2e8f0b3c`.
+pub fn uuid4(rng: &mut Rng) -> String {
+    let mut bytes = [0u8; 16];
+    rng.fill(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Generates `len` random lowercase hexadecimal digits.
+pub fn random_hex(rng: &mut Rng, len: usize) -> String {
+    (0..len)
+        .map(|_| char::from_digit((rng.next_u64() % 16) as u32, 16).unwrap())
+        .collect()
+}
+
+/// Generates a random URL-safe id of `len` characters.
+pub fn nanoid(rng: &mut Rng, len: usize) -> String {
+    (0..len)
+        .map(|_| NANOID_ALPHABET[(rng.next_u64() % 64) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_seeded_and_well_formed() {
+        let id = uuid4(&mut Rng::new(7));
+        assert_eq!(id, uuid4(&mut Rng::new(7)));
+        assert_ne!(id, uuid4(&mut Rng::new(8)));
+        let groups: Vec<usize> = id.split('-').map(str::len).collect();
+        assert_eq!(groups, [8, 4, 4, 4, 12]);
+        assert_eq!(&id[14..15], "4");
+        assert!("89ab".contains(&id[19..20]));
+
+        let mut rng = Rng::new(1);
+        let hex = random_hex(&mut rng, 10);
+        assert!(hex.len() == 10 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        let nanoid = nanoid(&mut rng, 21);
+        assert!(nanoid.len() == 21 && nanoid.bytes().all(|c| NANOID_ALPHABET.contains(&c)));
+    }
+}
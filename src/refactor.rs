@@ -0,0 +1,99 @@
+use crate::builtins::is_builtin;
+use crate::symbols::{analyze, SymbolKind};
+
+/// Words the grammar treats specially, which can't be used as names.
+pub(crate) const KEYWORDS: &[&str] = &[
+    "print", "let", "if", "else", "while", "break", "continue", "return", "fn", "match", "import",
+    "from", "as",
+];
+
+/// Renames the variable, parameter or function at a byte offset, updating its
+/// definitions and every reference to it.
+///
+/// Only the symbol under the cursor is renamed: a variable of the same name
+/// in another function body is a different symbol and is left alone, and so
+/// is text inside string literals.
+///
+/// # Arguments
+/// * `source` - The program's source code.
+/// * `offset` - A byte offset within a definition or use of the symbol.
+/// * `new_name` - The name to give it.
+///
+/// # Returns
+/// The edited source, or a message explaining why the rename isn't possible.
+pub fn rename(source: &str, offset: usize, new_name: &str) -> Result<String, String> {
+    let table = analyze(source).map_err(|e| format!("Parse error: {}", e))?;
+    let symbol = table
+        .symbol_at(offset)
+        .ok_or_else(|| "No variable or function to rename here".to_string())?;
+
+    let valid = !new_name.is_empty()
+        && new_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && new_name != "_";
+    if !valid || KEYWORDS.contains(&new_name) {
+        return Err(format!("'{}' is not a valid name", new_name));
+    }
+    let is_function = matches!(symbol.kind, SymbolKind::Function | SymbolKind::Import);
+    let clash = table.symbols.iter().any(|other| {
+        other.name == new_name
+            && matches!(other.kind, SymbolKind::Function | SymbolKind::Import) == is_function
+            && (is_function || other.scope == symbol.scope)
+    });
+    if clash || (is_function && is_builtin(new_name)) {
+        return Err(format!("'{}' is already defined", new_name));
+    }
+    if symbol.kind == SymbolKind::Import {
+        return Err(format!(
+            "'{}' is imported; rename it in its module instead",
+            symbol.name
+        ));
+    }
+
+    let mut spans: Vec<_> = std::iter::once(symbol.definition)
+        .chain(symbol.redefinitions.iter().copied())
+        .chain(symbol.references.iter().copied())
+        .collect();
+    spans.sort_by_key(|span| span.start);
+
+    let mut edited = String::new();
+    let mut copied = 0;
+    for span in spans {
+        edited.push_str(&source[copied..span.start]);
+        edited.push_str(new_name);
+        copied = span.end;
+    }
+    edited.push_str(&source[copied..]);
+    Ok(edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn area(w) {\n  let total = w * w\n  return total\n}\nlet total = area(3)\nprint(\"total\")\nprint(total)\n";
+
+    #[test]
+    fn test_rename_respects_scopes_and_strings() {
+        let offset = SOURCE.find("return total").unwrap() + "return ".len();
+        let renamed = rename(SOURCE, offset, "result").unwrap();
+        assert_eq!(
+            renamed,
+            "fn area(w) {\n  let result = w * w\n  return result\n}\nlet total = area(3)\nprint(\"total\")\nprint(total)\n"
+        );
+
+        let renamed = rename(SOURCE, SOURCE.find("area").unwrap(), "square").unwrap();
+        assert!(renamed.contains("fn square(w)") && renamed.contains("= square(3)"));
+    }
+
+    #[test]
+    fn test_rename_rejections() {
+        let top_level = SOURCE.rfind("total").unwrap();
+        assert!(rename(SOURCE, top_level, "let").unwrap_err().contains("not a valid name"));
+        let area = SOURCE.find("area").unwrap();
+        assert!(rename(SOURCE, area, "repeat").unwrap_err().contains("already defined"));
+        assert!(rename(SOURCE, SOURCE.find("print").unwrap(), "x").is_err());
+        // `w` exists only inside `area`, so the top-level variable can take it.
+        assert!(rename(SOURCE, top_level, "w").is_ok());
+        assert!(rename(SOURCE, SOURCE.find("w * w").unwrap(), "total").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::ast::{Context, Expr, Stmt};
+use crate::cfg::apply_defines;
+use crate::eval::{catch_runtime_error, eval_expr, exec_program, exec_stmt};
+use crate::include::resolve_includes;
+use crate::parser::{parse, parse_expr};
+
+/// Runs an interactive read-eval-print loop on stdin using the given context.
+///
+/// Input is collected until its braces balance, so blocks can span several
+/// lines. Statements are executed; a line that is only an expression has its
+/// value printed. Errors are reported and the loop carries on. The loop ends
+/// at end of input (Ctrl-D).
+///
+/// # Arguments
+/// * `ctx` - The context whose variables and functions the session works with.
+pub fn repl(ctx: &mut Context) {
+    let stdin = io::stdin();
+    let mut buffer = String::new();
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { "... " });
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => buffer.push_str(&line),
+        }
+        if !is_complete(&buffer) {
+            continue;
+        }
+
+        let input = std::mem::take(&mut buffer);
+        if !input.trim().is_empty() {
+            eval_input(&input, ctx);
+        }
+    }
+    println!();
+}
+
+/// The file that `dash repl` loads from the project directory before the
+/// session starts.
+pub const RC_FILE: &str = ".dashrc";
+
+/// Runs a script in `ctx` so that its variables and functions are available
+/// in a REPL session. Its imports and `include_str` calls are resolved
+/// relative to the file.
+///
+/// # Returns
+/// A message if the file can't be read or parsed, or fails at runtime.
+pub fn load_file(path: &Path, ctx: &mut Context) -> Result<(), String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Error reading file '{}': {}", path.display(), e))?
+        .replace("\r\n", "\n");
+    let program = parse(&source).map_err(|e| format!("Parse error in {}: {}", path.display(), e))?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut program = apply_defines(program, &ctx.defines);
+    resolve_includes(&mut program, &dir).map_err(|e| format!("{} in {}", e, path.display()))?;
+
+    let base_dir = std::mem::replace(&mut ctx.base_dir, dir);
+    let depth = ctx.frames.len();
+    let result = catch_runtime_error(|| exec_program(&program, ctx));
+    ctx.base_dir = base_dir;
+    result.map_err(|message| {
+        ctx.unwind_to(depth);
+        format!("Runtime error in {}: {}", path.display(), message)
+    })
+}
+
+/// Evaluates one complete piece of REPL input.
+///
+/// The input is first parsed as statements; if that fails, it is parsed as a
+/// single expression and the resulting value is printed. A lone call such as
+/// `random_hex(4)` parses as a statement but is evaluated like an expression,
+/// so its result is printed too, unless it is empty.
+///
+/// # Arguments
+/// * `input` - The source text entered by the user.
+/// * `ctx` - The context to evaluate the input in.
+pub fn eval_input(input: &str, ctx: &mut Context) {
+    let depth = ctx.frames.len();
+    let result = match parse(input) {
+        Ok(stmts) => match as_call_expr(&stmts) {
+            Some(expr) => catch_runtime_error(|| {
+                let value = eval_expr(&expr, ctx);
+                if !value.is_empty() {
+                    println!("{}", value);
+                }
+            }),
+            None => catch_runtime_error(|| {
+                for stmt in &stmts {
+                    exec_stmt(stmt, ctx);
+                }
+            }),
+        },
+        Err(stmt_error) => match parse_expr(input) {
+            Ok(expr) => catch_runtime_error(|| println!("{}", eval_expr(&expr, ctx))),
+            Err(_) => {
+                println!("Parse error: {}", stmt_error);
+                return;
+            }
+        },
+    };
+    if let Err(message) = result {
+        println!("Runtime error: {}", message);
+        ctx.unwind_to(depth);
+    }
+}
+
+/// Returns the expression to evaluate for input that is nothing but one call
+/// statement.
+fn as_call_expr(stmts: &[Stmt]) -> Option<Expr> {
+    match stmts {
+        [Stmt::Call(name, args)] => Some(Expr::Call(name.clone(), args.clone())),
+        [Stmt::Expr(expr)] => Some(expr.clone()),
+        _ => None,
+    }
+}
+
+/// Returns `true` once every opened brace in `input` has been closed.
+fn is_complete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    for c in input.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0 && !in_string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete("print(1)\n"));
+        assert!(!is_complete("while x < 3 {\n"));
+        assert!(!is_complete("print(\"a\n"));
+        assert!(is_complete("print(\"{\")\n"));
+    }
+
+    #[test]
+    fn test_eval_input_recovers_from_errors() {
+        let mut ctx = Context::default();
+        eval_input("fn f(a) {\n  print(nope)\n}\nlet x = 1\n", &mut ctx);
+        eval_input("f(2)\n", &mut ctx);
+        assert!(ctx.frames.is_empty());
+        assert_eq!(ctx.variables["x"], "1");
+    }
+
+    #[test]
+    fn test_lone_call_is_evaluated_as_expression() {
+        let stmts = parse("random_hex(4)\n").unwrap();
+        assert!(matches!(as_call_expr(&stmts), Some(Expr::Call(name, _)) if name == "random_hex"));
+        assert!(as_call_expr(&parse("print(1)\nprint(2)\n").unwrap()).is_none());
+        assert!(as_call_expr(&parse("let x = random_hex(4)\n").unwrap()).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A table of strings owned by the host and shared by any number of
+/// contexts, including ones running on other threads.
+///
+/// Cloning a `SharedTable` gives another handle to the same table, so data
+/// loaded once can be read by every script without being copied into each
+/// `Context`. Scripts reach a table through the name it is mounted under,
+/// with `shared_get`, `shared_set` and `shared_delete`; the host can read
+/// and change it at any time. Each access takes the table's lock only for
+/// that access.
+///
+/// ```
+/// use dash_lang::isolate::{IsolatePool, Limits};
+/// use dash_lang::shared::{Access, SharedTable};
+/// use dash_lang::ContextBuilder;
+/// use std::collections::HashMap;
+///
+/// let prices = SharedTable::from(HashMap::from([("apple".to_string(), "3".to_string())]));
+/// let builder = ContextBuilder::new().mount("prices", prices.clone(), Access::ReadOnly);
+/// let pool = IsolatePool::new(4, builder, Limits::default());
+/// let job = pool.submit("return shared_get(\"prices\", \"apple\") * 2", HashMap::new()).unwrap();
+/// assert_eq!(job.value, "6");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SharedTable(Arc<RwLock<HashMap<String, String>>>);
+
+/// What the scripts a table is mounted in may do with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Scripts can read entries.
+    ReadOnly,
+    /// Scripts can read, add, replace and delete entries.
+    ReadWrite,
+}
+
+impl SharedTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value stored under `key`, if any.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.read().get(key).cloned()
+    }
+
+    /// Stores `value` under `key`, returning the value it replaced.
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.write().insert(key.into(), value.into())
+    }
+
+    /// Removes `key`, returning its value if it was there.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        self.write().remove(key)
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns `true` if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    /// Runs `f` with the whole table, holding the lock for writing, so that
+    /// several changes are seen by scripts together or not at all.
+    pub fn update<R>(&self, f: impl FnOnce(&mut HashMap<String, String>) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    // A panic in an `update` closure poisons the lock, but the entries are
+    // still usable, so the poison is ignored.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, String>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl From<HashMap<String, String>> for SharedTable {
+    fn from(entries: HashMap<String, String>) -> Self {
+        SharedTable(Arc::new(RwLock::new(entries)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Context;
+    use crate::eval::catch_runtime_error;
+    use crate::parser::run_with_context;
+
+    #[test]
+    fn test_mounted_tables_are_shared_and_checked() {
+        let counts = SharedTable::new();
+        let config = SharedTable::from(HashMap::from([("mode".to_string(), "fast".to_string())]));
+        let mut first = Context::default();
+        first.mount("counts", counts.clone(), Access::ReadWrite);
+        first.mount("config", config.clone(), Access::ReadOnly);
+        let mut second = Context::default();
+        second.mount("counts", counts.clone(), Access::ReadWrite);
+
+        run_with_context("shared_set(\"counts\", \"hits\", 1)", &mut first);
+        run_with_context(
+            "let hits = shared_get(\"counts\", \"hits\") + 1\nshared_set(\"counts\", \"hits\", hits)\nlet gone = shared_get(\"counts\", \"misses\", \"none\")",
+            &mut second,
+        );
+        assert_eq!(counts.get("hits").as_deref(), Some("2"));
+        assert_eq!(second.variables["gone"], "none");
+
+        let err = catch_runtime_error(|| run_with_context("shared_set(\"config\", \"mode\", \"slow\")", &mut first));
+        assert_eq!(err.unwrap_err(), "Shared table 'config' is mounted read-only");
+        let err = catch_runtime_error(|| run_with_context("let x = shared_get(\"config\", \"mode\")", &mut second));
+        assert_eq!(err.unwrap_err(), "No shared table is mounted as 'config'");
+        assert_eq!(config.get("mode").as_deref(), Some("fast"));
+    }
+}
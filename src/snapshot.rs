@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ast::Context;
+use crate::cfg::apply_defines;
+use crate::eval::{catch_runtime_error, exec_program, run_exit_hooks};
+use crate::include::resolve_includes;
+use crate::output::Capture;
+use crate::parser::parse;
+
+/// Returns the file holding the expected output of `script`: the script's
+/// path with the extension `.out`.
+pub fn snapshot_path(script: &Path) -> PathBuf {
+    script.with_extension("out")
+}
+
+/// Runs a script the way `dash <file>` would and returns what it printed.
+///
+/// A runtime error doesn't fail the run: its message is added to the output
+/// as a `Runtime error: ...` line, so a snapshot can also record how a
+/// script fails.
+///
+/// # Arguments
+/// * `script` - The path of the script.
+///
+/// # Returns
+/// The printed output, or a message if the script couldn't be read or parsed.
+pub fn capture_output(script: &Path) -> Result<String, String> {
+    let source = fs::read_to_string(script)
+        .map_err(|e| format!("Error reading file '{}': {}", script.display(), e))?
+        .replace("\r\n", "\n");
+    let program = parse(&source).map_err(|e| format!("Parse error: {}", e))?;
+
+    let capture = Capture::default();
+    let mut ctx = Context::default();
+    ctx.output.sink = Box::new(capture.clone());
+    ctx.permissions.allow_fs = true;
+    if let Some(dir) = script.parent() {
+        ctx.base_dir = dir.to_path_buf();
+    }
+    let mut program = apply_defines(program, &ctx.defines);
+    resolve_includes(&mut program, &ctx.base_dir)?;
+
+    // The hooks run however the program stops, as they do under `dash`.
+    let result = catch_runtime_error(|| exec_program(&program, &mut ctx));
+    ctx.unwind_to(0);
+    let result = result.and(catch_runtime_error(|| run_exit_hooks(&mut ctx)));
+    let mut output = capture.contents();
+    if let Err(message) = result {
+        output.push_str(&format!("Runtime error: {}\n", message));
+    }
+    Ok(output)
+}
+
+/// Compares two texts line by line.
+///
+/// # Returns
+/// `None` if they are equal, otherwise every line of both, prefixed with
+/// `-` if only `expected` has it, `+` if only `actual` has it, and a space
+/// if both do.
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            out.push_str(&format!("  {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            out.push_str(&format!("- {}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", new[j]));
+            j += 1;
+        }
+    }
+    if old == new {
+        out.push_str("(the texts differ only in line endings)\n");
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_marks_changed_lines() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), None);
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nx\nc\nd\n").unwrap(),
+            "  a\n- b\n+ x\n  c\n+ d\n"
+        );
+    }
+
+    #[test]
+    fn test_capture_output_records_runtime_errors() {
+        let dir = std::env::temp_dir().join(format!("dash-snapshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("fails.dash");
+        fs::write(&script, "print(1)\nprint(missing)\n").unwrap();
+
+        let output = capture_output(&script).unwrap();
+        assert!(output.starts_with("1\nRuntime error: "), "{}", output);
+        assert_eq!(snapshot_path(&script), dir.join("fails.out"));
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Execution counters collected by the evaluator.
+///
+/// Counters accumulate for the lifetime of a `Context` and can be read with
+/// `Context::stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Statements executed, including those inside loops and functions.
+    pub statements: u64,
+    /// Calls to user-defined functions and builtins.
+    pub calls: u64,
+    /// Values produced by evaluating expressions. Every value is a freshly
+    /// allocated string, so this approximates allocations.
+    pub allocations: u64,
+    /// Bytes currently held by variables in all active scopes.
+    pub value_bytes: usize,
+    /// The highest `value_bytes` seen so far.
+    pub peak_value_bytes: usize,
+}
+
+impl Stats {
+    /// Records that a variable now holds `new` bytes instead of `old`.
+    ///
+    /// Variables inserted into `Context::variables` directly were never
+    /// counted, so replacing one must not take the total below zero.
+    pub(crate) fn replace_value(&mut self, old: usize, new: usize) {
+        self.value_bytes = self.value_bytes.saturating_sub(old) + new;
+        self.peak_value_bytes = self.peak_value_bytes.max(self.value_bytes);
+    }
+
+    /// Records that a scope holding `variables` has been dropped.
+    pub(crate) fn release_scope(&mut self, variables: &HashMap<String, String>) {
+        let bytes: usize = variables.values().map(String::len).sum();
+        self.value_bytes = self.value_bytes.saturating_sub(bytes);
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "statements executed: {}", self.statements)?;
+        writeln!(f, "function calls:      {}", self.calls)?;
+        writeln!(f, "values allocated:    {}", self.allocations)?;
+        write!(f, "peak value memory:   {} bytes", self.peak_value_bytes)
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::ast::Context;
+use crate::json::{quote, Reader};
+
+/// The file the `store_*` builtins use, in `Context::base_dir`, unless
+/// `Context::store_path` says otherwise.
+pub const DEFAULT_STORE_FILE: &str = ".dash-store.json";
+
+/// Returns the file holding the store of `ctx`.
+pub fn store_path(ctx: &Context) -> PathBuf {
+    ctx.store_path
+        .clone()
+        .unwrap_or_else(|| ctx.base_dir.join(DEFAULT_STORE_FILE))
+}
+
+/// Returns the value stored under `key`, if any.
+pub fn get(key: &str, ctx: &Context) -> Option<String> {
+    load(ctx).remove(key)
+}
+
+/// Stores `value` under `key`, replacing any value already there.
+pub fn set(key: &str, value: &str, ctx: &Context) {
+    let mut entries = load(ctx);
+    entries.insert(key.to_string(), value.to_string());
+    save(&entries, ctx);
+}
+
+/// Removes `key` from the store.
+///
+/// # Returns
+/// `true` if the key was there.
+pub fn delete(key: &str, ctx: &Context) -> bool {
+    let mut entries = load(ctx);
+    let found = entries.remove(key).is_some();
+    if found {
+        save(&entries, ctx);
+    }
+    found
+}
+
+/// Reads every entry of the store. A store that doesn't exist yet is empty.
+///
+/// The file is read on every access rather than cached, so runs of several
+/// scripts against the same store see each other's changes.
+fn load(ctx: &Context) -> BTreeMap<String, String> {
+    let path = store_path(ctx);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return BTreeMap::new(),
+        Err(e) => panic!("Cannot read store '{}': {}", path.display(), e),
+    };
+    parse_entries(&text).unwrap_or_else(|e| panic!("Invalid store '{}': {}", path.display(), e))
+}
+
+/// Writes every entry of the store, one per line in key order so the file
+/// diffs well. The file is replaced in one step, so a script stopped while
+/// saving leaves the previous contents intact.
+fn save(entries: &BTreeMap<String, String>, ctx: &Context) {
+    let path = store_path(ctx);
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|(key, value)| format!("  {}: {}", quote(key), quote(value)))
+        .collect();
+    let text = if lines.is_empty() {
+        "{}\n".to_string()
+    } else {
+        format!("{{\n{}\n}}\n", lines.join(",\n"))
+    };
+    let partial = path.with_extension(format!("tmp-{}", std::process::id()));
+    let written = fs::write(&partial, text).and_then(|()| fs::rename(&partial, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial);
+        panic!("Cannot write store '{}': {}", path.display(), e);
+    }
+}
+
+/// Parses a JSON object whose values are all strings.
+fn parse_entries(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut reader = Reader::new(text);
+    let mut entries = BTreeMap::new();
+    reader.expect('{')?;
+    if !reader.eat('}') {
+        loop {
+            let key = reader.string()?;
+            reader.expect(':')?;
+            entries.insert(key, reader.string()?);
+            if !reader.eat(',') {
+                break;
+            }
+        }
+        reader.expect('}')?;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_persist_between_contexts() {
+        let dir = std::env::temp_dir().join(format!("dash-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ctx = Context {
+            store_path: Some(dir.join("state.json")),
+            ..Context::default()
+        };
+        assert_eq!(get("runs", &ctx), None);
+        set("runs", "1", &ctx);
+        set("last \"note\"", "line one\nline two", &ctx);
+
+        let later = Context {
+            store_path: Some(dir.join("state.json")),
+            ..Context::default()
+        };
+        assert_eq!(get("runs", &later).as_deref(), Some("1"));
+        assert_eq!(get("last \"note\"", &later).as_deref(), Some("line one\nline two"));
+        assert!(delete("runs", &later));
+        assert!(!delete("runs", &later));
+        assert_eq!(
+            fs::read_to_string(dir.join("state.json")).unwrap(),
+            "{\n  \"last \\\"note\\\"\": \"line one\\nline two\"\n}\n"
+        );
+    }
+}
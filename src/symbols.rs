@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::builtins::constant;
+use crate::diagnostic::Span;
+use crate::parser::{DashParser, ParseError, Rule};
+
+/// What a symbol names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A variable created by `let` or by a binding pattern in a `match` arm.
+    Variable,
+    /// A function parameter.
+    Parameter,
+    /// A function defined with `fn`.
+    Function,
+    /// A function brought into scope with `from module import name`.
+    Import,
+}
+
+/// A defined name and every place it is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The function whose body the symbol belongs to, or `None` at top level.
+    /// Functions and imports are visible everywhere and always have `None`.
+    pub scope: Option<String>,
+    /// Where the name is first defined.
+    pub definition: Span,
+    /// Later definitions of the same name in the same scope, such as the
+    /// `let x = x + 1` in a loop.
+    pub redefinitions: Vec<Span>,
+    /// Every place the name is read or called.
+    pub references: Vec<Span>,
+}
+
+/// The symbols of a program, built by `analyze`.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    /// Variables read before any definition in their scope.
+    pub unresolved_variables: Vec<Unresolved>,
+    /// Calls to functions not defined in the program, such as builtins or
+    /// misspellings. Namespaced calls (`utils.parse`) are not listed.
+    pub unresolved_calls: Vec<Unresolved>,
+}
+
+/// A use of a name that `analyze` couldn't match to a definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unresolved {
+    pub name: String,
+    pub span: Span,
+    /// The function whose body the use is in, or `None` at top level.
+    pub scope: Option<String>,
+}
+
+impl SymbolTable {
+    /// Finds the symbol defined, redefined or referenced at a byte offset,
+    /// which is how go-to-definition and rename find what the cursor is on.
+    pub fn symbol_at(&self, offset: usize) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| {
+            std::iter::once(&symbol.definition)
+                .chain(&symbol.redefinitions)
+                .chain(&symbol.references)
+                .any(|span| span.start <= offset && offset <= span.end)
+        })
+    }
+
+    /// Returns the symbols with no references.
+    pub fn unused(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .iter()
+            .filter(|symbol| symbol.references.is_empty())
+    }
+}
+
+/// Builds the symbol table of a program.
+///
+/// Variables belong to the function body (or top level) they are assigned
+/// in, since a function call only sees its own parameters and `let`s. A
+/// variable is resolved when it is read after its first definition in that
+/// scope; functions resolve regardless of where they are defined.
+///
+/// # Arguments
+/// * `source` - The program's source code.
+///
+/// # Returns
+/// The symbol table, or the error if the program doesn't parse.
+pub fn analyze(source: &str) -> Result<SymbolTable, ParseError> {
+    let program = DashParser::parse(Rule::program, source)
+        .map_err(Box::new)?
+        .next()
+        .unwrap();
+    let mut analyzer = Analyzer::default();
+    analyzer.visit(program);
+
+    for call in std::mem::take(&mut analyzer.calls) {
+        match analyzer.functions.get(&call.name) {
+            Some(&index) => analyzer.table.symbols[index].references.push(call.span),
+            None => analyzer.table.unresolved_calls.push(call),
+        }
+    }
+    Ok(analyzer.table)
+}
+
+/// State for a single pass over the parse tree.
+#[derive(Default)]
+struct Analyzer {
+    table: SymbolTable,
+    /// The function body being visited, or `None` at top level.
+    scope: Option<String>,
+    /// Variables and parameters of the current scope, by name.
+    variables: HashMap<String, usize>,
+    /// Functions and imports, by name.
+    functions: HashMap<String, usize>,
+    /// Calls, resolved once every function definition has been seen.
+    calls: Vec<Unresolved>,
+}
+
+impl Analyzer {
+    fn visit(&mut self, pair: Pair<Rule>) {
+        match pair.as_rule() {
+            Rule::let_stmt => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                self.visit(inner.next().unwrap());
+                self.define_variable(name, SymbolKind::Variable);
+            }
+            Rule::fn_stmt => {
+                let outer_scope = self.scope.take();
+                let outer_variables = std::mem::take(&mut self.variables);
+                for part in pair.into_inner() {
+                    match part.as_rule() {
+                        Rule::ident => {
+                            self.define_function(part.clone(), SymbolKind::Function);
+                            self.scope = Some(part.as_str().to_string());
+                        }
+                        Rule::param_list => {
+                            for param in part.into_inner() {
+                                self.define_variable(param, SymbolKind::Parameter);
+                            }
+                        }
+                        Rule::block => self.visit(part),
+                        _ => {}
+                    }
+                }
+                self.scope = outer_scope;
+                self.variables = outer_variables;
+            }
+            Rule::from_stmt => {
+                for name in pair.into_inner().skip(1) {
+                    self.define_function(name, SymbolKind::Import);
+                }
+            }
+            Rule::import_stmt => {}
+            Rule::pattern => {
+                let pattern = pair.into_inner().next().unwrap();
+                if pattern.as_rule() == Rule::ident {
+                    self.define_variable(pattern, SymbolKind::Variable);
+                }
+            }
+            Rule::call_expr => {
+                let mut inner = pair.into_inner();
+                let name = inner.next().unwrap();
+                if !name.as_str().contains('.') {
+                    let call = self.unresolved(&name);
+                    self.calls.push(call);
+                }
+                for arg in inner {
+                    self.visit(arg);
+                }
+            }
+            Rule::ident => match self.variables.get(pair.as_str()) {
+                Some(&index) => self.table.symbols[index]
+                    .references
+                    .push(pair.as_span().into()),
+                None if constant(pair.as_str()).is_some() => {}
+                None => {
+                    let variable = self.unresolved(&pair);
+                    self.table.unresolved_variables.push(variable);
+                }
+            },
+            _ => {
+                for inner in pair.into_inner() {
+                    self.visit(inner);
+                }
+            }
+        }
+    }
+
+    /// Describes a use of `name` in the current scope.
+    fn unresolved(&self, name: &Pair<Rule>) -> Unresolved {
+        Unresolved {
+            name: name.as_str().to_string(),
+            span: name.as_span().into(),
+            scope: self.scope.clone(),
+        }
+    }
+
+    /// Records a variable or parameter definition in the current scope.
+    fn define_variable(&mut self, name: Pair<Rule>, kind: SymbolKind) {
+        let span = Span::from(name.as_span());
+        if let Some(&index) = self.variables.get(name.as_str()) {
+            self.table.symbols[index].redefinitions.push(span);
+            return;
+        }
+        let index = self.push(name.as_str(), kind, self.scope.clone(), span);
+        self.variables.insert(name.as_str().to_string(), index);
+    }
+
+    /// Records a function or import definition.
+    fn define_function(&mut self, name: Pair<Rule>, kind: SymbolKind) {
+        let span = Span::from(name.as_span());
+        if let Some(&index) = self.functions.get(name.as_str()) {
+            self.table.symbols[index].redefinitions.push(span);
+            return;
+        }
+        let index = self.push(name.as_str(), kind, None, span);
+        self.functions.insert(name.as_str().to_string(), index);
+    }
+
+    fn push(&mut self, name: &str, kind: SymbolKind, scope: Option<String>, definition: Span) -> usize {
+        self.table.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            scope,
+            definition,
+            redefinitions: Vec::new(),
+            references: Vec::new(),
+        });
+        self.table.symbols.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "fn add(a, b) {\n  let sum = a + b\n  return sum\n}\nlet x = 1\nlet x = add(x, y)\nprint(add(x, 2))\nlog_info(\"done\")\n";
+
+    #[test]
+    fn test_definitions_and_references() {
+        let table = analyze(SOURCE).unwrap();
+        let names: Vec<(&str, SymbolKind, Option<&str>)> = table
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.scope.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("add", SymbolKind::Function, None),
+                ("a", SymbolKind::Parameter, Some("add")),
+                ("b", SymbolKind::Parameter, Some("add")),
+                ("sum", SymbolKind::Variable, Some("add")),
+                ("x", SymbolKind::Variable, None),
+            ]
+        );
+
+        let add = &table.symbols[0];
+        assert_eq!(add.references.iter().map(|s| s.line).collect::<Vec<_>>(), [6, 7]);
+        let x = &table.symbols[4];
+        assert_eq!(x.definition.line, 5);
+        assert_eq!(x.redefinitions[0].line, 6);
+        assert_eq!(x.references.len(), 2);
+
+        assert_eq!(table.unresolved_variables[0].name, "y");
+        assert_eq!(table.unresolved_calls[0].name, "log_info");
+        assert_eq!(table.unused().count(), 0);
+    }
+
+    #[test]
+    fn test_symbol_at_offset() {
+        let table = analyze(SOURCE).unwrap();
+        let offset = SOURCE.find("return sum").unwrap() + "return ".len() + 1;
+        let symbol = table.symbol_at(offset).unwrap();
+        assert_eq!(symbol.name, "sum");
+        assert_eq!(symbol.definition.line, 2);
+        assert!(table.symbol_at(SOURCE.find("print").unwrap()).is_none());
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::ast::Context;
+use crate::random::nanoid;
+
+/// Creates an empty temporary file or directory and records it on `ctx`,
+/// so `remove_temp_paths` deletes it when the script exits.
+///
+/// # Arguments
+/// * `dir` - Create a directory instead of a file.
+/// * `suffix` - Appended to the generated name, such as `.csv`.
+/// * `ctx` - The context that owns the new path.
+///
+/// # Returns
+/// The path of the new file or directory.
+pub fn create_temp(dir: bool, suffix: &str, ctx: &mut Context) -> PathBuf {
+    let name = format!("dash-{}-{}{}", std::process::id(), nanoid(&mut ctx.rng, 12), suffix);
+    let path = std::env::temp_dir().join(name);
+    let created = if dir {
+        fs::create_dir(&path)
+    } else {
+        fs::File::create_new(&path).map(drop)
+    };
+    if let Err(e) = created {
+        panic!("Cannot create temporary path '{}': {}", path.display(), e);
+    }
+    ctx.temp_paths.push(path.clone());
+    path
+}
+
+/// Deletes the temporary files and directories created by `temp_file` and
+/// `temp_dir`, including anything the script put inside the directories.
+/// Paths that are already gone are skipped.
+pub fn remove_temp_paths(ctx: &mut Context) {
+    for path in ctx.temp_paths.drain(..).rev() {
+        let _ = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{ContextBuilder, Permissions};
+    use crate::eval::{catch_runtime_error, run_exit_hooks};
+    use crate::parser::run_with_context;
+
+    #[test]
+    fn test_temp_paths_removed_at_exit() {
+        let mut ctx = ContextBuilder::new()
+            .permissions(Permissions {
+                allow_fs: true,
+                ..Permissions::default()
+            })
+            .build();
+        run_with_context("let file = temp_file(\".csv\")\nlet dir = temp_dir()\n", &mut ctx);
+        let file = std::path::PathBuf::from(&ctx.variables["file"]);
+        let dir = std::path::PathBuf::from(&ctx.variables["dir"]);
+        assert!(file.is_file() && file.to_string_lossy().ends_with(".csv"));
+        assert!(dir.is_dir());
+        std::fs::write(dir.join("inner.txt"), "x").unwrap();
+
+        run_exit_hooks(&mut ctx);
+        assert!(!file.exists() && !dir.exists());
+        assert!(ctx.temp_paths.is_empty());
+
+        // A failing hook doesn't keep the files around.
+        run_with_context("let file = temp_file()\non_exit(\"missing\")\n", &mut ctx);
+        let file = std::path::PathBuf::from(&ctx.variables["file"]);
+        assert!(catch_runtime_error(|| run_exit_hooks(&mut ctx)).is_err());
+        assert!(!file.exists());
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Width of the bar drawn by `progress_tick`, in characters.
+const BAR_WIDTH: usize = 30;
+
+/// Colors accepted by `term_color`, with their ANSI foreground codes.
+const COLORS: &[(&str, u8)] = &[
+    ("black", 30),
+    ("red", 31),
+    ("green", 32),
+    ("yellow", 33),
+    ("blue", 34),
+    ("magenta", 35),
+    ("cyan", 36),
+    ("white", 37),
+];
+
+/// A progress bar started by `progress_start`.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub total: u64,
+    pub done: u64,
+}
+
+/// Returns `true` if escape codes and progress bars should be written to
+/// stdout: it is a terminal, and `NO_COLOR` isn't set.
+pub fn enabled() -> bool {
+    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Renders a progress bar such as `[=========>                    ] 3/10`.
+pub fn render_bar(done: u64, total: u64) -> String {
+    let filled = if total == 0 {
+        BAR_WIDTH
+    } else {
+        (done.min(total) as usize * BAR_WIDTH) / total as usize
+    };
+    let head = if filled < BAR_WIDTH { ">" } else { "" };
+    format!(
+        "[{}{}{}] {}/{}",
+        "=".repeat(filled),
+        head,
+        " ".repeat(BAR_WIDTH - filled - head.len()),
+        done,
+        total
+    )
+}
+
+/// Redraws the progress bar in place, ending the line once it is full.
+pub fn draw_progress(progress: &Progress) {
+    if !enabled() {
+        return;
+    }
+    let mut out = io::stdout();
+    let _ = write!(out, "\r{}", render_bar(progress.done, progress.total));
+    if progress.done >= progress.total {
+        let _ = writeln!(out);
+    }
+    let _ = out.flush();
+}
+
+/// Clears the terminal and moves the cursor to the top left.
+pub fn clear() {
+    if enabled() {
+        print!("\x1b[2J\x1b[H");
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Wraps `text` in the escape codes for `color`, or returns it unchanged
+/// when stdout isn't a terminal.
+///
+/// # Returns
+/// The text, or `None` if `color` isn't one of the supported names.
+pub fn color(text: &str, color: &str) -> Option<String> {
+    let (_, code) = COLORS.iter().find(|(name, _)| *name == color)?;
+    Some(if enabled() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    })
+}
+
+/// Names of the colors `term_color` accepts, for error messages.
+pub fn color_names() -> String {
+    COLORS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bar() {
+        assert_eq!(render_bar(0, 3), format!("[>{}] 0/3", " ".repeat(29)));
+        assert_eq!(render_bar(1, 2), format!("[{}>{}] 1/2", "=".repeat(15), " ".repeat(14)));
+        assert_eq!(render_bar(5, 5), format!("[{}] 5/5", "=".repeat(30)));
+        assert_eq!(render_bar(0, 0), format!("[{}] 0/0", "=".repeat(30)));
+        assert!(color("x", "purple").is_none());
+    }
+}
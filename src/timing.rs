@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::ast::{Context, LoopControl, Stmt};
+use crate::format::stmt_summary;
+
+/// How many statements the report shows.
+const REPORT_LIMIT: usize = 20;
+
+/// Wall time spent in each statement of a run, collected for `--time-lines`
+/// when `Context::line_times` is set.
+///
+/// A statement's time is its self time: time spent in the statements nested
+/// in it, such as a loop's body or a called function's body, is counted
+/// against those statements instead, so the report points at the statements
+/// that do the work rather than the loops that contain them.
+#[derive(Debug, Clone, Default)]
+pub struct LineTimes {
+    /// Timings by the address of the statement. Function bodies are shared
+    /// between calls rather than copied, so this stays put while the program
+    /// runs.
+    entries: HashMap<usize, LineTime>,
+    /// Time spent so far in statements nested in the one running.
+    nested: Duration,
+}
+
+/// The time spent in one statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineTime {
+    /// The first line of the statement, formatted.
+    pub statement: String,
+    /// The function the statement belongs to, or `None` at the top level.
+    pub function: Option<String>,
+    /// How many times the statement ran.
+    pub runs: u64,
+    /// Time spent in the statement itself.
+    pub time: Duration,
+}
+
+impl LineTimes {
+    /// Returns the statements that ran, slowest first.
+    pub fn hottest(&self) -> Vec<&LineTime> {
+        let mut times: Vec<&LineTime> = self.entries.values().collect();
+        times.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.statement.cmp(&b.statement)));
+        times
+    }
+
+    /// Total time across all statements.
+    pub fn total(&self) -> Duration {
+        self.entries.values().map(|entry| entry.time).sum()
+    }
+}
+
+impl fmt::Display for LineTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        write!(f, "hot statements by self time (total {:.2?}):", total)?;
+        write!(f, "\n{:>10} {:>6} {:>8}  statement", "time", "share", "runs")?;
+        for entry in self.hottest().into_iter().take(REPORT_LIMIT) {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                entry.time.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            write!(
+                f,
+                "\n{:>10} {:>5.1}% {:>8}  {}",
+                format!("{:.2?}", entry.time),
+                share,
+                entry.runs,
+                entry.statement
+            )?;
+            if let Some(function) = &entry.function {
+                write!(f, "  (in {})", function)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `stmt` with `run`, adding its self time to `ctx.line_times`.
+pub(crate) fn timed(stmt: &Stmt, ctx: &mut Context, run: fn(&Stmt, &mut Context) -> LoopControl) -> LoopControl {
+    let Some(times) = ctx.line_times.as_mut() else {
+        return run(stmt, ctx);
+    };
+    let outer = std::mem::take(&mut times.nested);
+    let start = Instant::now();
+    let result = run(stmt, ctx);
+    let elapsed = start.elapsed();
+
+    let function = ctx.frames.last().map(|frame| &frame.function);
+    let Some(times) = ctx.line_times.as_mut() else {
+        return result;
+    };
+    let nested = std::mem::replace(&mut times.nested, outer + elapsed);
+    let entry = times
+        .entries
+        .entry(stmt as *const Stmt as usize)
+        .or_insert_with(|| LineTime {
+            statement: stmt_summary(stmt),
+            function: function.cloned(),
+            runs: 0,
+            time: Duration::ZERO,
+        });
+    entry.runs += 1;
+    entry.time += elapsed.saturating_sub(nested);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::run_with_context;
+
+    #[test]
+    fn test_time_is_attributed_to_innermost_statements() {
+        let mut ctx = Context {
+            line_times: Some(LineTimes::default()),
+            ..Context::default()
+        };
+        run_with_context(
+            "fn nap() {\n  sleep(5)\n}\nlet i = 0\nwhile i < 3 {\n  nap()\n  let i = i + 1\n}\n",
+            &mut ctx,
+        );
+        let times = ctx.line_times.unwrap();
+        let hottest = times.hottest();
+        assert_eq!(hottest[0].statement, "sleep(5)");
+        assert_eq!(hottest[0].function.as_deref(), Some("nap"));
+        assert_eq!(hottest[0].runs, 3);
+        assert!(hottest[0].time >= Duration::from_millis(15));
+
+        let header = hottest.iter().find(|t| t.statement == "while i < 3 {").unwrap();
+        assert_eq!(header.runs, 1);
+        assert!(header.time < Duration::from_millis(5));
+        assert!(times.to_string().contains("(in nap)"));
+    }
+
+    #[test]
+    fn test_statements_of_different_functions_are_kept_apart() {
+        let mut ctx = Context {
+            line_times: Some(LineTimes::default()),
+            ..Context::default()
+        };
+        run_with_context(
+            "fn a() {\n  sleep(1)\n}\nfn b() {\n  let q = 1\n}\nlet i = 0\nwhile i < 5 {\n  a()\n  b()\n  let i = i + 1\n}\n",
+            &mut ctx,
+        );
+        let times = ctx.line_times.unwrap();
+        let hottest = times.hottest();
+        let runs = |statement: &str| hottest.iter().find(|t| t.statement == statement).map(|t| t.runs);
+        assert_eq!(runs("sleep(1)"), Some(5));
+        assert_eq!(runs("let q = 1"), Some(5));
+    }
+}
@@ -0,0 +1,137 @@
+//! Human-readable durations and byte sizes.
+
+/// Suffixes of `format_bytes`, in steps of 1024.
+const BINARY_UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Duration units accepted by `parse_duration`, in milliseconds. `ms` comes
+/// before `m` so it is matched first.
+const DURATION_UNITS: &[(&str, f64)] = &[
+    ("ms", 1.0),
+    ("s", 1_000.0),
+    ("m", 60_000.0),
+    ("h", 3_600_000.0),
+    ("d", 86_400_000.0),
+];
+
+/// Formats milliseconds as e.g. `850ms`, `2.5s`, `2m 13s` or `1h 5m`.
+///
+/// Durations of a minute or more show the two largest units, dropping the
+/// rest.
+pub fn format_duration(ms: i64) -> String {
+    let sign = if ms < 0 { "-" } else { "" };
+    let ms = ms.unsigned_abs();
+    if ms < 1_000 {
+        return format!("{}{}ms", sign, ms);
+    }
+    if ms < 60_000 {
+        let seconds = format!("{:.1}", ms as f64 / 1_000.0);
+        return format!("{}{}s", sign, seconds.trim_end_matches(".0"));
+    }
+    let parts = [
+        (ms / 86_400_000, "d"),
+        (ms / 3_600_000 % 24, "h"),
+        (ms / 60_000 % 60, "m"),
+        (ms / 1_000 % 60, "s"),
+    ];
+    let first = parts.iter().position(|(n, _)| *n > 0).unwrap();
+    let mut text = format!("{}{}{}", sign, parts[first].0, parts[first].1);
+    if let Some((n, unit)) = parts.get(first + 1).filter(|(n, _)| *n > 0) {
+        text.push_str(&format!(" {}{}", n, unit));
+    }
+    text
+}
+
+/// Parses a duration such as `1h30m`, `90s`, `1.5s` or `250ms`.
+///
+/// # Returns
+/// The duration in milliseconds, rounded, or `None` if `text` isn't a
+/// sequence of numbers with units.
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let number: f64 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let (unit, ms) = DURATION_UNITS.iter().find(|(unit, _)| rest.starts_with(unit))?;
+        total += number * ms;
+        rest = rest[unit.len()..].trim_start();
+    }
+    Some(total.round() as i64)
+}
+
+/// Formats a byte count as e.g. `512 B` or `4.2 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < BINARY_UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, BINARY_UNITS[unit])
+}
+
+/// Parses a size such as `4.2 MiB`, `10KB` or `512`.
+///
+/// `KiB`, `MiB`, ... are powers of 1024 and `KB`, `MB`, ... powers of 1000;
+/// a bare number is bytes. Units are case-insensitive.
+///
+/// # Returns
+/// The size in bytes, rounded, or `None` if `text` isn't a size.
+pub fn parse_bytes(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let digits = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let number: f64 = text[..digits].parse().ok()?;
+    let unit = text[digits..].trim().to_lowercase();
+    let multiplier = match unit.as_str() {
+        "" | "b" => 1.0,
+        _ => {
+            let prefix = "kmgtpe".find(unit.chars().next()?)? as i32 + 1;
+            match &unit[1..] {
+                "ib" => 1024f64.powi(prefix),
+                "b" => 1000f64.powi(prefix),
+                _ => return None,
+            }
+        }
+    };
+    Some((number * multiplier).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(format_duration(850), "850ms");
+        assert_eq!(format_duration(2_500), "2.5s");
+        assert_eq!(format_duration(13_000), "13s");
+        assert_eq!(format_duration(133_000), "2m 13s");
+        assert_eq!(format_duration(120_000), "2m");
+        assert_eq!(format_duration(3_723_000), "1h 2m");
+        assert_eq!(format_duration(-90_000), "-1m 30s");
+        assert_eq!(parse_duration("1h30m"), Some(5_400_000));
+        assert_eq!(parse_duration("1.5s 250ms"), Some(1_750));
+        assert_eq!(parse_duration("2d"), Some(172_800_000));
+        assert_eq!(parse_duration("90"), None);
+        assert_eq!(parse_duration("5 weeks"), None);
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(4_404_019), "4.2 MiB");
+        assert_eq!(parse_bytes("4.2 MiB"), Some(4_404_019));
+        assert_eq!(parse_bytes("10KB"), Some(10_000));
+        assert_eq!(parse_bytes("1 gib"), Some(1 << 30));
+        assert_eq!(parse_bytes("512"), Some(512));
+        assert_eq!(parse_bytes("3 XB"), None);
+    }
+}
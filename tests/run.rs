@@ -1,4 +1,10 @@
-use dash_lang::run;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use dash_lang::{
+    catch_runtime_error, exec_program, parse, parse_with_diagnostics, resolve_includes, run, run_with_context, ContextBuilder,
+    Permissions, Severity,
+};
 
 #[test]
 fn test_run_simple_program() {
@@ -10,3 +16,186 @@ fn test_run_simple_program() {
     // You can redirect stdout to capture output if needed
     run(source);
 }
+
+#[test]
+fn test_disabled_builtin_is_undefined() {
+    let mut ctx = ContextBuilder::new()
+        .without_builtin("log_info")
+        .prelude("let greeting = \"hi\"")
+        .build();
+    run_with_context("let seen = greeting", &mut ctx);
+    assert_eq!(ctx.variables["seen"], "hi");
+
+    let err = catch_runtime_error(|| run_with_context("log_info(\"x\")", &mut ctx)).unwrap_err();
+    assert_eq!(err, "Undefined function: log_info");
+}
+
+#[test]
+fn test_text_builtins() {
+    let mut ctx = ContextBuilder::new().build();
+    run_with_context(
+        "let id = pad_left(42, 5, \"0\")\nlet name = pad_right(\"né\", 4, \".\")\nlet rule = repeat(\"-=\", 3)\nlet wide = pad_left(\"abcdef\", 3, \" \")\n",
+        &mut ctx,
+    );
+    assert_eq!(ctx.variables["id"], "00042");
+    assert_eq!(ctx.variables["name"], "né..");
+    assert_eq!(ctx.variables["rule"], "-=-=-=");
+    assert_eq!(ctx.variables["wide"], "abcdef");
+
+    let err = catch_runtime_error(|| run_with_context("let x = pad_left(\"a\", 3, \"ab\")", &mut ctx)).unwrap_err();
+    assert!(err.contains("single fill character"));
+}
+
+#[test]
+fn test_hash_and_encoding_builtins() {
+    let mut ctx = ContextBuilder::new().build();
+    run_with_context(
+        "let digest = sha256(\"abc\")\nlet encoded = base64_encode(\"hello\")\nlet decoded = base64_decode(encoded)\nlet same = secure_equals(decoded, \"hello\")\nlet differ = secure_equals(\"hello\", \"help\")\nlet bytes = hex(\"A\")\nlet quick = hash(\"\")\n",
+        &mut ctx,
+    );
+    assert_eq!(ctx.variables["digest"], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(ctx.variables["encoded"], "aGVsbG8=");
+    assert_eq!(ctx.variables["decoded"], "hello");
+    assert_eq!(ctx.variables["same"], "1");
+    assert_eq!(ctx.variables["differ"], "0");
+    assert_eq!(ctx.variables["bytes"], "41");
+    assert_eq!(ctx.variables["quick"], "cbf29ce484222325");
+}
+
+#[test]
+fn test_path_builtins() {
+    let mut ctx = ContextBuilder::new().build();
+    run_with_context(
+        "let path = path_join(\"out\", \"2024\", \"report.tar.gz\")\nlet base = basename(path)\nlet dir = dirname(path)\nlet ext = extension(path)\nlet none = extension(dir)\n",
+        &mut ctx,
+    );
+    let separator = std::path::MAIN_SEPARATOR;
+    assert_eq!(ctx.variables["path"], format!("out{0}2024{0}report.tar.gz", separator));
+    assert_eq!(ctx.variables["base"], "report.tar.gz");
+    assert_eq!(ctx.variables["dir"], format!("out{}2024", separator));
+    assert_eq!(ctx.variables["ext"], "gz");
+    assert_eq!(ctx.variables["none"], "");
+
+    let err = catch_runtime_error(|| run_with_context("let files = glob(\"*.dash\")", &mut ctx)).unwrap_err();
+    assert!(err.contains("Permissions::allow_fs"));
+}
+
+#[test]
+fn test_parse_reports_every_syntax_error() {
+    let source = "let a = 1\nlet = 2\nwhile a < {\n  print(a)\n}\nprint(a)\nprint(";
+    let (statements, errors) = parse_with_diagnostics(source);
+    assert_eq!(statements.len(), 2);
+    let lines: Vec<usize> = errors.iter().map(|e| e.span.line).collect();
+    assert_eq!(lines, [2, 3, 7]);
+    assert!(errors.iter().all(|e| e.severity == Severity::Error));
+}
+
+#[test]
+fn test_eval_needs_permission() {
+    let source = "let rule = \"limit * 2\"\nlet limit = 21\nlet doubled = eval(rule)\neval(\"fn triple(x) {\n  return x * 3\n}\")\nlet tripled = triple(limit)\n";
+    let mut ctx = ContextBuilder::new().build();
+    let err = catch_runtime_error(|| run_with_context(source, &mut ctx)).unwrap_err();
+    assert!(err.contains("Permissions::allow_eval"));
+
+    let mut ctx = ContextBuilder::new()
+        .permissions(Permissions {
+            allow_eval: true,
+            ..Permissions::default()
+        })
+        .build();
+    run_with_context(source, &mut ctx);
+    assert_eq!(ctx.variables["doubled"], "42");
+    assert_eq!(ctx.variables["tripled"], "63");
+}
+
+#[test]
+fn test_sleep_stops_at_deadline() {
+    let mut ctx = ContextBuilder::new().build();
+    let start = Instant::now();
+    ctx.deadline = Some(start + Duration::from_millis(50));
+    let err = catch_runtime_error(|| run_with_context("sleep(10000)\nlet after = 1", &mut ctx)).unwrap_err();
+    assert_eq!(err, "Interrupted");
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(!ctx.variables.contains_key("after"));
+}
+
+#[test]
+fn test_integer_limits() {
+    let mut ctx = ContextBuilder::new().build();
+    run_with_context(
+        "let a = saturating_add(INT_MAX, 1)\nlet b = saturating_mul(INT_MIN, 2)\nlet c = saturating_sub(5, 7)\n",
+        &mut ctx,
+    );
+    assert_eq!(ctx.variables["a"], "9223372036854775807");
+    assert_eq!(ctx.variables["b"], "-9223372036854775808");
+    assert_eq!(ctx.variables["c"], "-2");
+
+    let err = catch_runtime_error(|| run_with_context("let d = INT_MAX + 1", &mut ctx)).unwrap_err();
+    assert_eq!(err, "Integer overflow in 9223372036854775807 + 1");
+
+    for (source, message) in [
+        ("let e = 1 / 0", "Division by zero in 1 / 0"),
+        ("let e = 1 // 0", "Division by zero in 1 // 0"),
+        ("let e = INT_MIN / -1", "Integer overflow in -9223372036854775808 / -1"),
+        ("let e = INT_MIN // -1", "Integer overflow in -9223372036854775808 // -1"),
+    ] {
+        let err = catch_runtime_error(|| run_with_context(source, &mut ctx)).unwrap_err();
+        assert_eq!(err, message);
+    }
+}
+
+#[test]
+fn test_diff_and_assert_eq() {
+    let mut ctx = ContextBuilder::new().build();
+    run_with_context(
+        "let same = diff(\"a\", \"a\")\nlet changed = diff(\"name\nage\", \"name\nsize\")\nassert_eq(same, \"\")\n",
+        &mut ctx,
+    );
+    assert_eq!(ctx.variables["same"], "");
+    assert_eq!(ctx.variables["changed"], "  name\n- age\n+ size\n");
+
+    let err = catch_runtime_error(|| run_with_context("assert_eq(1 + 1, 3)", &mut ctx)).unwrap_err();
+    assert_eq!(err, "assert_eq failed: left is \"2\", right is \"3\"");
+    let err = catch_runtime_error(|| run_with_context("assert_eq(\"a\nb\", \"a\nc\")", &mut ctx)).unwrap_err();
+    assert_eq!(err, "assert_eq failed (- left, + right):\n  a\n- b\n+ c");
+}
+
+#[test]
+fn test_trace_statement_from_the_command_line() {
+    let path = std::env::temp_dir().join(format!("dash-trace-{}.dash", std::process::id()));
+    std::fs::write(&path, "let x = 1\ntrace(x)\nprint(\"after\")\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_dash")).arg(&path).output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "after\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "[2:1] x = 1\n");
+}
+
+#[test]
+fn test_include_str_from_a_string_needs_permission() {
+    let dir = std::env::temp_dir().join(format!("dash-include-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("greeting.txt"), "hello").unwrap();
+    let source = "let text = include_str(\"greeting.txt\")";
+
+    let mut ctx = ContextBuilder::new().build();
+    ctx.base_dir = dir.clone();
+    let err = catch_runtime_error(|| run_with_context(source, &mut ctx)).unwrap_err();
+    assert!(err.contains("Permissions::allow_fs"), "{}", err);
+
+    let mut program = parse(source).unwrap();
+    resolve_includes(&mut program, &dir).unwrap();
+    exec_program(&program, &mut ctx);
+    assert_eq!(ctx.variables["text"], "hello");
+
+    let mut ctx = ContextBuilder::new()
+        .permissions(Permissions {
+            allow_fs: true,
+            ..Permissions::default()
+        })
+        .build();
+    ctx.base_dir = dir.clone();
+    run_with_context(source, &mut ctx);
+    assert_eq!(ctx.variables["text"], "hello");
+    std::fs::remove_dir_all(&dir).unwrap();
+}